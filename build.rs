@@ -3,4 +3,6 @@ fn main() {
         "cargo:rustc-env=TARGET={}",
         std::env::var("TARGET").unwrap()
     );
+    // minisign public keys trusted in addition to the upstream ones
+    println!("cargo:rerun-if-env-changed=JORUP_TRUSTED_KEYS");
 }
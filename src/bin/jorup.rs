@@ -6,20 +6,34 @@ use jorup::{
 use std::{
     env::{self, consts::EXE_SUFFIX},
     ffi::OsStr,
+    path::Path,
 };
 use structopt::StructOpt;
 
 fn main() {
-    let current_executable = env::current_exe().expect("Failed to get current executable name");
-    let current_executable = current_executable.file_name().unwrap();
+    let executable = env::current_exe().expect("Failed to get current executable name");
+    let current_executable = executable.file_name().unwrap();
     let init_name = format!("jorup-init{}", EXE_SUFFIX);
     if current_executable == OsStr::new(&init_name) {
         run(commands::Install::from_args())
+    } else if let Some(tool) = utils::proxy::tool_name(&executable) {
+        run_proxy(tool, &executable)
     } else {
         run(commands::RootCmd::from_args())
     }
 }
 
+fn run_proxy(tool: &str, executable: &Path) {
+    let args = env::args_os().skip(1).collect();
+    match utils::proxy::run(tool, executable, args) {
+        Ok(code) => std::process::exit(code),
+        Err(error) => {
+            utils::print_error(error);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn run(app: impl Cmd) {
     if let Err(error) = app.run() {
         utils::print_error(error);
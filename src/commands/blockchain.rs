@@ -1,6 +1,11 @@
 use crate::{
     common::JorupConfig,
-    utils::download::{self, Client},
+    config,
+    utils::{
+        download::{self, Client},
+        registry::{self, Registry},
+        version::VersionReq,
+    },
 };
 use structopt::StructOpt;
 use thiserror::Error;
@@ -10,8 +15,67 @@ use thiserror::Error;
 pub enum Command {
     /// Download the latest config for blockchains
     Update,
-    /// List blockchains from `jorfile.json`
+    /// List blockchains from `jorfile.json` and the registries
     List,
+    /// Manage the jorfile registries merged over the upstream jorfile
+    Source(Source),
+    /// Define a blockchain locally, without editing a jorfile. Local
+    /// definitions take precedence over the jorfiles
+    Add {
+        name: String,
+
+        /// The hash of the genesis block, 64 hexadecimal characters
+        #[structopt(long)]
+        block0_hash: String,
+
+        /// The address of a trusted peer, such as /ip4/1.2.3.4/tcp/3000.
+        /// Can be given several times
+        #[structopt(long = "peer", number_of_values = 1, required = true)]
+        peers: Vec<String>,
+
+        /// The jormungandr versions compatible with the blockchain, such as
+        /// ">=0.9"
+        #[structopt(long)]
+        versions: VersionReq,
+
+        #[structopt(long, default_value = "")]
+        description: String,
+    },
+    /// Change a blockchain defined locally. A blockchain of a jorfile is
+    /// copied to the local definitions first
+    Edit {
+        name: String,
+
+        /// The hash of the genesis block, 64 hexadecimal characters
+        #[structopt(long)]
+        block0_hash: Option<String>,
+
+        /// Replace the trusted peers. Can be given several times
+        #[structopt(long = "peer", number_of_values = 1)]
+        peers: Vec<String>,
+
+        /// The jormungandr versions compatible with the blockchain
+        #[structopt(long)]
+        versions: Option<VersionReq>,
+
+        #[structopt(long)]
+        description: Option<String>,
+    },
+    /// Remove a blockchain defined locally
+    Remove { name: String },
+}
+
+/// Jorfile registries, such as one listing private testnets. A registry
+/// takes precedence over the ones added after it and over the upstream
+/// jorfile
+#[derive(Debug, StructOpt)]
+pub enum Source {
+    /// Add a jorfile registry from a URL or a local file
+    Add { name: String, location: String },
+    /// List the jorfile registries in priority order
+    List,
+    /// Remove a jorfile registry
+    Remove { name: String },
 }
 
 #[derive(Debug, Error)]
@@ -22,12 +86,29 @@ pub enum Error {
     JorfileLoadFailed(#[source] crate::common::Error),
     #[error("Failed to create the downloader client")]
     DownloaderCreate(#[source] download::Error),
+    #[error("Cannot lock the jorup home")]
+    Lock(#[source] crate::utils::lock::Error),
+    #[error("Cannot load the jorfile registries")]
+    Registries(#[source] crate::common::Error),
+    #[error("Cannot update the jorfile registries")]
+    Registry(#[source] registry::Error),
+    #[error("Cannot update the local blockchains")]
+    LocalBlockchains(#[source] crate::common::Error),
+    #[error("Invalid blockchain definition")]
+    Invalid(#[source] config::Error),
+    #[error("Blockchain {0} is already defined locally, use `jorup blockchain edit`")]
+    AlreadyDefined(String),
+    #[error("No blockchain named {0}")]
+    NotFound(String),
+    #[error("Blockchain {0} is not defined locally")]
+    NotDefinedLocally(String),
 }
 
 impl Command {
     pub fn run(self, mut cfg: JorupConfig) -> Result<(), Error> {
         match self {
             Command::Update => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
                 let mut client = Client::new().map_err(Error::DownloaderCreate)?;
                 cfg.sync_jorfile(&mut client).map_err(Error::SyncFailed)?;
             }
@@ -35,13 +116,163 @@ impl Command {
                 let config = cfg.load_jor().map_err(Error::JorfileLoadFailed)?;
                 for blockchain in config.blockchains().iter() {
                     println!(
-                        "\t{}\nGenesis block hash: {}\n{}\n",
+                        "\t{}\nGenesis block hash: {}\nSource: {}\n{}\n",
                         blockchain.name(),
                         blockchain.block0_hash(),
+                        blockchain.origin(),
                         blockchain.description()
                     );
                 }
             }
+            Command::Source(source) => source.run(cfg)?,
+            Command::Add {
+                name,
+                block0_hash,
+                peers,
+                versions,
+                description,
+            } => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
+                let blockchain = config::Blockchain::new(
+                    name.clone(),
+                    description,
+                    versions,
+                    block0_hash,
+                    peers,
+                )
+                .map_err(Error::Invalid)?;
+
+                let mut local = cfg.local_blockchains().map_err(Error::LocalBlockchains)?;
+                if local.get_blockchain(&name).is_some() {
+                    return Err(Error::AlreadyDefined(name));
+                }
+                if let Ok(jor) = cfg.load_jor() {
+                    if let Some(existing) = jor.get_blockchain(&name) {
+                        eprintln!(
+                            "WARN: overriding the {} blockchain of the {} jorfile",
+                            name,
+                            existing.origin()
+                        );
+                    }
+                }
+                local.set(blockchain);
+                cfg.save_local_blockchains(&local)
+                    .map_err(Error::LocalBlockchains)?;
+                println!("**** blockchain {} added", name);
+            }
+            Command::Edit {
+                name,
+                block0_hash,
+                peers,
+                versions,
+                description,
+            } => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
+                let mut local = cfg.local_blockchains().map_err(Error::LocalBlockchains)?;
+                let mut blockchain = match local.get_blockchain(&name) {
+                    Some(blockchain) => blockchain.clone(),
+                    None => {
+                        let blockchain = cfg
+                            .load_jor()
+                            .map_err(Error::JorfileLoadFailed)?
+                            .get_blockchain(&name)
+                            .cloned()
+                            .ok_or_else(|| Error::NotFound(name.clone()))?;
+                        println!(
+                            "**** copying the {} blockchain of the {} jorfile",
+                            name,
+                            blockchain.origin()
+                        );
+                        blockchain
+                    }
+                };
+
+                if let Some(block0_hash) = block0_hash {
+                    blockchain = blockchain
+                        .with_block0_hash(block0_hash)
+                        .map_err(Error::Invalid)?;
+                }
+                if !peers.is_empty() {
+                    blockchain = blockchain
+                        .with_trusted_peers(peers)
+                        .map_err(Error::Invalid)?;
+                }
+                if let Some(versions) = versions {
+                    blockchain = blockchain.with_jormungandr_versions(versions);
+                }
+                if let Some(description) = description {
+                    blockchain = blockchain.with_description(description);
+                }
+
+                local.set(blockchain);
+                cfg.save_local_blockchains(&local)
+                    .map_err(Error::LocalBlockchains)?;
+                println!("**** blockchain {} updated", name);
+            }
+            Command::Remove { name } => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
+                let mut local = cfg.local_blockchains().map_err(Error::LocalBlockchains)?;
+                if local.remove(&name).is_none() {
+                    if let Some(blockchain) = cfg
+                        .load_jor()
+                        .ok()
+                        .and_then(|jor| jor.get_blockchain(&name))
+                    {
+                        eprintln!(
+                            "HINT: {} comes from the {} jorfile",
+                            name,
+                            blockchain.origin()
+                        );
+                    }
+                    return Err(Error::NotDefinedLocally(name));
+                }
+                cfg.save_local_blockchains(&local)
+                    .map_err(Error::LocalBlockchains)?;
+                println!("**** blockchain {} removed", name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source {
+    fn run(self, cfg: JorupConfig) -> Result<(), Error> {
+        match self {
+            Source::Add { name, location } => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
+                let registry = Registry::new(name, &location).map_err(Error::Registry)?;
+                let mut registries = cfg.registries().map_err(Error::Registries)?;
+                registries.add(registry.clone()).map_err(Error::Registry)?;
+
+                if cfg.offline() && !registry.is_local() {
+                    eprintln!("WARN: the registry will be synced when online");
+                    eprintln!("HINT: run `jorup blockchain update`");
+                } else {
+                    let mut client = Client::new().map_err(Error::DownloaderCreate)?;
+                    cfg.sync_registry(&mut client, &registry)
+                        .map_err(Error::SyncFailed)?;
+                }
+                registries
+                    .save(&cfg.registries_file())
+                    .map_err(Error::Registry)?;
+                println!("**** registry {} added", registry.name());
+            }
+            Source::List => {
+                for registry in cfg.registries().map_err(Error::Registries)?.iter() {
+                    println!("{}\t{}", registry.name(), registry.url());
+                }
+                println!("{}\t{}", registry::UPSTREAM, registry::UPSTREAM_URL);
+            }
+            Source::Remove { name } => {
+                let _lock = cfg.lock().map_err(Error::Lock)?;
+                let mut registries = cfg.registries().map_err(Error::Registries)?;
+                let registry = registries.remove(&name).map_err(Error::Registry)?;
+                registries
+                    .save(&cfg.registries_file())
+                    .map_err(Error::Registry)?;
+                let _ = std::fs::remove_file(registry.cache_file(&cfg.cache_dir()));
+                println!("**** registry {} removed", registry.name());
+            }
         }
         Ok(())
     }
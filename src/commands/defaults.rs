@@ -1,4 +1,8 @@
-use crate::{common::JorupConfig, jormungandr_config as config, utils::blockchain::Blockchain};
+use crate::{
+    common::JorupConfig,
+    jormungandr_config as config,
+    utils::{blockchain::Blockchain, pin},
+};
 use structopt::StructOpt;
 use thiserror::Error;
 
@@ -6,8 +10,10 @@ use thiserror::Error;
 /// configuration can be customized and provided to `jorup run` later.
 #[derive(Debug, StructOpt)]
 pub struct Command {
-    /// The blockchain to get the configuration for
-    blockchain: String,
+    /// The blockchain to get the configuration for. If not specified, the
+    /// blockchain pinned in a `.jorup-version` or `jorup.toml` file of the
+    /// current directory or its parents is used
+    blockchain: Option<String>,
 
     #[structopt(long, default_value = "yaml")]
     format: ConfigFormat,
@@ -15,6 +21,8 @@ pub struct Command {
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("Cannot determine the blockchain to use")]
+    Pin(#[source] pin::Error),
     #[error("Cannot run the node without valid blockchain")]
     NoValidBlockchain(#[source] crate::utils::blockchain::Error),
     #[error("Could not write JSON")]
@@ -37,8 +45,10 @@ impl Command {
     pub fn run(&self, mut cfg: JorupConfig) -> Result<(), Error> {
         use std::net::ToSocketAddrs;
 
+        let (blockchain_name, pin) =
+            pin::select_blockchain(self.blockchain.clone()).map_err(Error::Pin)?;
         let blockchain =
-            Blockchain::load(&mut cfg, &self.blockchain).map_err(Error::NoValidBlockchain)?;
+            Blockchain::load(&mut cfg, &blockchain_name).map_err(Error::NoValidBlockchain)?;
         blockchain.prepare().map_err(Error::NoValidBlockchain)?;
 
         let output = config::Config {
@@ -52,7 +62,9 @@ impl Command {
                 trusted_peers: blockchain.entry().trusted_peers().to_vec(),
             }),
             rest: Some(config::Rest {
-                listen: "127.0.0.1:8080".to_socket_addrs().unwrap().next().unwrap(),
+                listen: pin
+                    .and_then(|pin| pin.rest_listen())
+                    .unwrap_or_else(|| "127.0.0.1:8080".to_socket_addrs().unwrap().next().unwrap()),
             }),
             storage: Some(blockchain.get_node_storage()),
             secret_files: vec![blockchain.get_node_secret()],
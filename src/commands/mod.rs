@@ -47,6 +47,14 @@ pub struct RootCmd {
     #[structopt(long)]
     offline: bool,
 
+    /// Where to fetch the releases from
+    ///
+    /// Either `github` (the default), the URL of a mirror serving
+    /// `<repo>/releases.json` indexes, or a local directory with the same
+    /// layout. Overrides `release_source` from `$JORUP_HOME/settings.json`.
+    #[structopt(long)]
+    release_source: Option<String>,
+
     #[structopt(subcommand)]
     command: Command,
 }
@@ -99,10 +107,16 @@ impl Cmd for RootCmd {
     type Err = Error;
 
     fn run(self) -> Result<(), Self::Err> {
-        let cfg = crate::common::JorupConfig::new(self.jorup_home, self.jorfile, self.offline)?;
+        let cfg = crate::common::JorupConfig::new(
+            self.jorup_home,
+            self.jorfile,
+            self.offline,
+            self.release_source,
+        )?;
+        cfg.detect_installed_path();
 
         if !self.offline && !matches!(self.command, Command::Setup(_)) {
-            match crate::utils::check_jorup_update() {
+            match crate::utils::check_jorup_update_quickly(&cfg) {
                 Ok(Some(release)) => {
                     eprintln!(
                         r#"
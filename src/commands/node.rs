@@ -2,12 +2,17 @@ use crate::{
     common::JorupConfig,
     utils::{
         blockchain::Blockchain,
-        download::{self, Client},
-        github,
-        release::{list_installed_releases, Error as ReleaseError, Release},
-        version::{Version, VersionReq},
+        download, github, proxy,
+        release::{self, list_installed_releases, Error as ReleaseError, LocalSource, Release},
+        runner,
+        signature::{self, TrustedKeys},
+        source_build::{self, SourceTree},
+        target::{AssetKind, Target},
+        version::{Channel, Version, VersionReq},
     },
 };
+use indicatif::HumanBytes;
+use std::path::PathBuf;
 use structopt::StructOpt;
 use thiserror::Error;
 
@@ -26,30 +31,133 @@ pub enum Command {
         #[structopt(short, long)]
         blockchain: Option<String>,
 
+        /// Install the latest release of the given channel: stable,
+        /// prerelease (or rc) or nightly. Defaults to stable
+        #[structopt(long, conflicts_with_all = &["version-req", "blockchain"])]
+        channel: Option<Channel>,
+
         /// Make the installed version default
         #[structopt(long)]
         make_default: bool,
+
+        /// Install the release even if its archive is not signed by a
+        /// trusted key
+        #[structopt(long)]
+        allow_unsigned: bool,
+
+        /// Download the release built for the given target triple instead
+        /// of the one jorup was built for
+        #[structopt(long)]
+        target: Option<Target>,
+
+        /// Install the release from a local archive instead of downloading
+        /// it. The version is detected from the binaries or the archive name
+        /// unless given with --version
+        #[structopt(long, conflicts_with_all = &["from-dir", "blockchain"])]
+        from_archive: Option<PathBuf>,
+
+        /// Install the release from a directory containing jormungandr and
+        /// jcli, for example a cargo build output. The version is detected
+        /// from the binaries unless given with --version
+        #[structopt(long, conflicts_with_all = &["from-archive", "blockchain"])]
+        from_dir: Option<PathBuf>,
+
+        /// Build the release from the jormungandr git repository at the
+        /// given URL. The version is the crate version with the commit hash
+        /// as build metadata (for example 0.9.0+1a2b3c4) unless given with
+        /// --version
+        #[structopt(
+            long,
+            conflicts_with_all = &["from-archive", "from-dir", "from-path", "blockchain"]
+        )]
+        from_git: Option<String>,
+
+        /// The git revision (commit, tag or branch) to build with --from-git,
+        /// defaults to the repository's default branch
+        #[structopt(long, requires = "from-git")]
+        rev: Option<String>,
+
+        /// Build the release from a local jormungandr source tree
+        #[structopt(long, conflicts_with_all = &["from-archive", "from-dir", "blockchain"])]
+        from_path: Option<PathBuf>,
     },
     /// List locally installed Jormungandr releases
-    List,
-    /// Remove the specified release
-    Remove { version: Version },
+    List {
+        /// List the releases available from the release source instead,
+        /// with their publication date, whether they have an asset for this
+        /// platform, whether they are installed and which blockchains of the
+        /// jorfile they are compatible with
+        #[structopt(long)]
+        remote: bool,
+    },
+    /// Make an installed release the one run by `jormungandr` and `jcli`,
+    /// without going to the network. Prints the current default when no
+    /// version is given
+    #[structopt(alias = "use")]
+    Default { version_req: Option<VersionReq> },
+    /// Show how and when an installed release was installed
+    Show { version: Version },
+    /// Check the installed releases: archive checksum, binaries and the
+    /// version they report, and the `jormungandr` and `jcli` proxies. Exits
+    /// with a non-zero status when a problem is found
+    Verify {
+        /// Only check this release
+        version: Option<Version>,
+
+        /// Also check the archives against the checksums published with the
+        /// releases
+        #[structopt(long)]
+        upstream: bool,
+    },
+    /// Remove installed releases according to a retention policy. The
+    /// default release and the releases of running nodes are always kept
+    Prune {
+        /// Keep only this number of the most recent nightly releases
+        #[structopt(long)]
+        keep_nightlies: Option<usize>,
+
+        /// Keep only the releases compatible with a blockchain of the
+        /// jorfile
+        #[structopt(long)]
+        keep_compatible: bool,
+
+        /// Remove the releases not used for this number of days
+        #[structopt(long)]
+        unused_days: Option<u32>,
+
+        /// Only report what would be removed and the disk space reclaimed
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Remove the installed releases matching a version requirement. The
+    /// default release and the releases of running nodes are only removed
+    /// with --force
+    Remove {
+        version_req: VersionReq,
+
+        /// Stop the nodes running a release being removed and make the
+        /// latest remaining release the default
+        #[structopt(long)]
+        force: bool,
+    },
 }
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Cannot run this command offline")]
-    Offline,
     #[error("Cannot load the requested blockchain")]
     NoValidBlockchain(#[from] crate::utils::blockchain::Error),
-    #[error("Cannot find a release on GitHub")]
+    #[error("Cannot find a matching release")]
     GitHub(#[from] crate::utils::github::Error),
     #[error("Cannot specify blockchain and version at the same time")]
     MustNotSpecifyBlockchainAndVersion,
     #[error("Failed to load a release")]
     ReleaseLoad(#[source] ReleaseError),
-    #[error("Cannot download and install an update")]
-    CannotUpdate(#[source] download::Error),
+    #[error("Installation failed while downloading the release archive")]
+    Download(#[source] download::Error),
+    #[error("Installation failed while verifying the release archive")]
+    Verify(#[source] ReleaseError),
+    #[error("Cannot install the release")]
+    Install(#[source] ReleaseError),
     #[error("Error while listing releases")]
     ReleasesList(#[source] ReleaseError),
     #[error("Failed to remove a release")]
@@ -58,18 +166,116 @@ pub enum Error {
     DownloaderCreate(#[source] download::Error),
     #[error("Error while creating directory: {1}")]
     CannotCreateDirectory(#[source] std::io::Error, std::path::PathBuf),
+    #[error("Cannot load the trusted public keys")]
+    TrustedKeys(#[source] signature::Error),
+    #[error("Refusing to install {0}: no signature from a trusted key, use `--allow-unsigned` to install anyway")]
+    Unsigned(String),
+    #[error("An exact version is expected, got {0}")]
+    VersionMustBeExact(VersionReq),
+    #[error("Cannot build the release from source")]
+    Build(#[source] source_build::Error),
+    #[error("No default release, set one with `jorup node default <version>`")]
+    NoDefault,
+    #[error("{0} problem(s) found")]
+    Unhealthy(usize),
+    #[error("No retention policy given, use --keep-nightlies, --keep-compatible or --unused-days")]
+    NoPrunePolicy,
+    #[error("Cannot load the jorfile")]
+    Jorfile(#[source] crate::common::Error),
+    #[error("Cannot check the running nodes")]
+    Runner(#[source] runner::Error),
+    #[error("Refusing to remove releases in use, use `--force` to stop their nodes and change the default release")]
+    ReleasesInUse,
+    #[error("Cannot lock the jorup home")]
+    Lock(#[source] crate::utils::lock::Error),
+    #[error("Cannot stop the {0} node")]
+    Shutdown(String, #[source] ShutdownError),
+}
+
+/// why a node using a release being removed could not be stopped
+#[derive(Debug, Error)]
+pub enum ShutdownError {
+    #[error(transparent)]
+    Blockchain(#[from] crate::utils::blockchain::Error),
+    #[error(transparent)]
+    Runner(#[from] runner::Error),
 }
 
 impl Command {
     pub fn run(self, cfg: JorupConfig) -> Result<(), Error> {
+        let _lock = match self {
+            Command::List { .. } | Command::Show { .. } | Command::Verify { .. } => None,
+            Command::Default { version_req: None } => None,
+            _ => Some(cfg.lock().map_err(Error::Lock)?),
+        };
+
         match self {
             Command::Install {
                 version_req,
                 blockchain,
+                channel,
                 make_default,
-            } => install(cfg, version_req, blockchain, make_default),
-            Command::List => list(cfg),
-            Command::Remove { version } => remove(cfg, version),
+                allow_unsigned,
+                target,
+                from_archive,
+                from_dir,
+                from_git,
+                rev,
+                from_path,
+            } => {
+                if let Some(archive) = from_archive {
+                    install_local(
+                        cfg,
+                        LocalSource::Archive(&archive),
+                        None,
+                        version_req,
+                        make_default,
+                    )
+                } else if let Some(dir) = from_dir {
+                    install_local(
+                        cfg,
+                        LocalSource::Directory(&dir),
+                        None,
+                        version_req,
+                        make_default,
+                    )
+                } else if let Some(url) = from_git {
+                    let tree =
+                        SourceTree::checkout(&cfg, &url, rev.as_deref()).map_err(Error::Build)?;
+                    let origin = match &rev {
+                        Some(rev) => format!("git+{}#{}", url, rev),
+                        None => format!("git+{}", url),
+                    };
+                    install_from_source(cfg, tree, origin, version_req, make_default)
+                } else if let Some(path) = from_path {
+                    let origin = path.display().to_string();
+                    let tree = SourceTree::open(path).map_err(Error::Build)?;
+                    install_from_source(cfg, tree, origin, version_req, make_default)
+                } else {
+                    let target = target.unwrap_or_else(Target::current);
+                    install(
+                        cfg,
+                        version_req,
+                        blockchain,
+                        channel.unwrap_or(Channel::Stable),
+                        make_default,
+                        allow_unsigned,
+                        &target,
+                    )
+                }
+            }
+            Command::List { remote: false } => list(cfg),
+            Command::List { remote: true } => list_remote(cfg),
+            Command::Default { version_req } => default(cfg, version_req),
+            Command::Show { version } => show(cfg, version),
+            Command::Verify { version, upstream } => verify(cfg, version, upstream),
+            Command::Prune {
+                keep_nightlies,
+                keep_compatible,
+                unused_days,
+                dry_run,
+            } => prune(cfg, keep_nightlies, keep_compatible, unused_days, dry_run),
+            Command::Remove { version_req, force } => remove(cfg, version_req, force),
         }
     }
 }
@@ -78,12 +284,11 @@ fn install(
     mut cfg: JorupConfig,
     version_req: Option<VersionReq>,
     blockchain: Option<String>,
+    channel: Channel,
     make_default: bool,
+    allow_unsigned: bool,
+    target: &Target,
 ) -> Result<(), Error> {
-    if cfg.offline() {
-        return Err(Error::Offline);
-    }
-
     if version_req.is_some() && blockchain.is_some() {
         return Err(Error::MustNotSpecifyBlockchainAndVersion);
     }
@@ -92,7 +297,7 @@ fn install(
 
     let version_req = match version_req {
         None => match blockchain {
-            None => VersionReq::Latest,
+            None => VersionReq::latest(channel),
             Some(blockchain_name) => Blockchain::load(&mut cfg, &blockchain_name)?
                 .jormungandr_version_req()
                 .clone(),
@@ -100,18 +305,25 @@ fn install(
         Some(version_req) => version_req,
     };
 
-    let mut client = Client::new().map_err(Error::DownloaderCreate)?;
+    let mut client = cfg.client().map_err(Error::DownloaderCreate)?;
 
     let release = if load_latest {
-        let gh_release =
-            github::find_matching_release(&mut client, github::JORMUNGANDR, version_req)?;
+        let gh_release = github::find_matching_release(
+            cfg.release_source(),
+            &mut client,
+            github::JORMUNGANDR,
+            version_req,
+        )?;
         Release::new_unchecked(&cfg, gh_release.version().clone())
     } else {
         match Release::load(&cfg, &version_req) {
-            Ok(release) => {
+            // a dated nightly requirement is satisfied by the installed
+            // build, only the rolling nightly needs refreshing
+            Ok(release) if matches!(version_req, VersionReq::Nightly) => {
                 if let Some(date) = release.version().get_nightly_date() {
                     if date < &chrono::Utc::now().date() {
                         let gh_release = github::find_matching_release(
+                            cfg.release_source(),
                             &mut client,
                             github::JORMUNGANDR,
                             version_req,
@@ -124,36 +336,118 @@ fn install(
                     release
                 }
             }
+            Ok(release) => release,
             Err(ReleaseError::NoCompatibleReleaseInstalled(_)) => {
-                let gh_release =
-                    github::find_matching_release(&mut client, github::JORMUNGANDR, version_req)?;
+                let gh_release = github::find_matching_release(
+                    cfg.release_source(),
+                    &mut client,
+                    github::JORMUNGANDR,
+                    version_req,
+                )?;
                 Release::new_unchecked(&cfg, gh_release.version().clone())
             }
             Err(err) => return Err(Error::ReleaseLoad(err)),
         }
     };
 
+    if !release.asset_need_open() {
+        println!("**** release {} already installed", release.version());
+        if make_default {
+            release.make_default(&cfg).map_err(Error::ReleaseLoad)?;
+        }
+        return Ok(());
+    }
+
     let asset = release
-        .asset_remote(&mut client)
+        .asset_remote(cfg.release_source(), &mut client, target)
         .map_err(Error::ReleaseLoad)?;
 
-    if release.asset_need_fetched() {
-        std::fs::create_dir_all(release.dir())
-            .map_err(|e| Error::CannotCreateDirectory(e, release.dir().clone()))?;
+    let mut checksums: Vec<String> = asset.sha256().into_iter().map(str::to_owned).collect();
+    checksums.extend(jorfile_checksum(&mut cfg, asset.name()));
+
+    let keys = TrustedKeys::load(&cfg).map_err(Error::TrustedKeys)?;
+    let signature = asset.signature().filter(|_| !keys.is_empty());
+    if keys.is_empty() {
+        eprintln!(
+            "WARN: no trusted signing key configured, the signature of {} is not checked",
+            asset.name()
+        );
+    } else if signature.is_none() {
+        if !allow_unsigned {
+            return Err(Error::Unsigned(asset.name().to_owned()));
+        }
+        eprintln!(
+            "WARN: {} is not signed by a trusted key, installing anyway",
+            asset.name()
+        );
+    }
+
+    // a partial download is kept around so it can be resumed
+    let archive = release.download_path(&cfg, asset.name());
+    if !archive.is_file() {
+        if let Some(download_dir) = archive.parent() {
+            std::fs::create_dir_all(download_dir)
+                .map_err(|e| Error::CannotCreateDirectory(e, download_dir.to_path_buf()))?;
+        }
         client
-            .download_file(
-                &release.get_asset().display().to_string(),
-                &asset.as_ref(),
-                release.get_asset(),
-            )
-            .map_err(|e| {
-                std::fs::remove_dir_all(release.dir()).expect("could not remove the release dir");
-                Error::CannotUpdate(e)
-            })?;
+            .download_file(asset.name(), asset.url(), &archive)
+            .map_err(Error::Download)?;
         println!("**** asset downloaded");
     }
 
-    release.asset_open().map_err(Error::ReleaseLoad)?;
+    if checksums.is_empty() {
+        eprintln!(
+            "WARN: no checksum published for {}, cannot verify the download",
+            asset.name()
+        );
+    } else {
+        release::verify_checksum(&archive, &checksums).map_err(Error::Verify)?;
+    }
+
+    if let Some(signature) = signature {
+        release::verify_signature(&archive, &keys, signature).map_err(Error::Verify)?;
+    }
+
+    // replace what may be left of an installation made before installs
+    // were staged
+    let installed = Release::install(
+        &cfg,
+        LocalSource::Archive(&archive),
+        Some(release.version().clone()),
+        asset.url().to_owned(),
+        target,
+        true,
+    );
+    // the download is complete, keeping it would only reuse a broken
+    // archive on the next attempt
+    if let Some(download_dir) = archive.parent() {
+        let _ = std::fs::remove_dir_all(download_dir);
+    }
+    let release = installed.map_err(Error::Install)?;
+    println!("**** release {} installed", release.version());
+
+    if make_default {
+        release.make_default(&cfg).map_err(Error::ReleaseLoad)?;
+    }
+
+    Ok(())
+}
+
+fn install_local(
+    cfg: JorupConfig,
+    source: LocalSource,
+    origin: Option<String>,
+    version_req: Option<VersionReq>,
+    make_default: bool,
+) -> Result<(), Error> {
+    let version = exact_version(version_req)?;
+    let origin = origin.unwrap_or_else(|| match source {
+        LocalSource::Archive(path) | LocalSource::Directory(path) => path.display().to_string(),
+    });
+
+    let release = Release::install(&cfg, source, version, origin, &Target::current(), false)
+        .map_err(Error::Install)?;
+    println!("**** release {} installed", release.version());
 
     if make_default {
         release.make_default(&cfg).map_err(Error::ReleaseLoad)?;
@@ -162,17 +456,455 @@ fn install(
     Ok(())
 }
 
+fn install_from_source(
+    cfg: JorupConfig,
+    tree: SourceTree,
+    origin: String,
+    version_req: Option<VersionReq>,
+    make_default: bool,
+) -> Result<(), Error> {
+    let bin_dir = tree.build().map_err(Error::Build)?;
+    let version = match exact_version(version_req)? {
+        Some(version) => version,
+        None => tree.version(&bin_dir).map_err(Error::Build)?,
+    };
+
+    install_local(
+        cfg,
+        LocalSource::Directory(&bin_dir),
+        Some(origin),
+        Some(VersionReq::exact(version)),
+        make_default,
+    )
+}
+
+fn exact_version(version_req: Option<VersionReq>) -> Result<Option<Version>, Error> {
+    match version_req {
+        None => Ok(None),
+        Some(version_req) => version_req
+            .clone()
+            .into_version()
+            .map(Some)
+            .ok_or(Error::VersionMustBeExact(version_req)),
+    }
+}
+
+/// look for a digest of the given asset in the local jorfile, if any
+fn jorfile_checksum(cfg: &mut JorupConfig, asset_name: &str) -> Option<String> {
+    if !cfg.jorfile().is_file() {
+        return None;
+    }
+    cfg.load_jor()
+        .ok()?
+        .blockchains()
+        .iter()
+        .find_map(|blockchain| blockchain.asset_checksum(asset_name))
+        .map(str::to_ascii_lowercase)
+}
+
 fn list(cfg: JorupConfig) -> Result<(), Error> {
+    let default = Release::current_default(&cfg);
     for release in list_installed_releases(&cfg).map_err(Error::ReleasesList)? {
-        println!("{}", release.version());
+        if default.as_ref().map(Release::version) == Some(release.version()) {
+            println!("{} (default)", release.version());
+        } else {
+            println!("{}", release.version());
+        }
+    }
+    Ok(())
+}
+
+fn list_remote(mut cfg: JorupConfig) -> Result<(), Error> {
+    let mut client = cfg.client().map_err(Error::DownloaderCreate)?;
+    let releases = github::list_releases(cfg.release_source(), &mut client, github::JORMUNGANDR)?;
+    let installed: Vec<Version> = list_installed_releases(&cfg)
+        .map_err(Error::ReleasesList)?
+        .into_iter()
+        .map(|release| release.version().clone())
+        .collect();
+
+    let blockchains: Vec<(String, VersionReq)> = match cfg.load_jor() {
+        Ok(jor) => jor
+            .blockchains()
+            .iter()
+            .map(|blockchain| {
+                (
+                    blockchain.name().to_owned(),
+                    blockchain.jormungandr_versions().clone(),
+                )
+            })
+            .collect(),
+        Err(err) => {
+            eprintln!("WARN: cannot load the jorfile, blockchain compatibility is not shown");
+            crate::utils::print_error(err);
+            Vec::new()
+        }
+    };
+
+    let target = Target::current();
+    println!(
+        "{:<20} {:<10} {:<5} {:<9} BLOCKCHAINS",
+        "VERSION", "PUBLISHED", "ASSET", "INSTALLED"
+    );
+    for release in releases {
+        let compatible: Vec<&str> = blockchains
+            .iter()
+            .filter(|(_, version_req)| version_req.matches(release.version()))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let line = format!(
+            "{:<20} {:<10} {:<5} {:<9} {}",
+            release.version().to_string(),
+            release.published_at().format("%Y-%m-%d").to_string(),
+            yes_no(release.get_asset(&target, AssetKind::Archive).is_some()),
+            yes_no(installed.contains(release.version())),
+            compatible.join(", ")
+        );
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn default(cfg: JorupConfig, version_req: Option<VersionReq>) -> Result<(), Error> {
+    let version_req = match version_req {
+        Some(version_req) => version_req,
+        None => {
+            let release = Release::current_default(&cfg).ok_or(Error::NoDefault)?;
+            println!("{}", release.version());
+            return Ok(());
+        }
+    };
+
+    let release = Release::load(&cfg, &version_req).map_err(|err| {
+        eprintln!("HINT: run `jorup node install -v {}`", version_req);
+        Error::ReleaseLoad(err)
+    })?;
+    release.make_default(&cfg).map_err(Error::ReleaseLoad)?;
+    println!("**** {} is now the default release", release.version());
+
+    Ok(())
+}
+
+fn show(cfg: JorupConfig, version: Version) -> Result<(), Error> {
+    let release = Release::load(&cfg, &VersionReq::exact(version)).map_err(Error::ReleaseLoad)?;
+    println!("version:      {}", release.version());
+    println!("directory:    {}", release.dir().display());
+
+    let manifest = match release.manifest().map_err(Error::ReleaseLoad)? {
+        Some(manifest) => manifest,
+        None => {
+            eprintln!("WARN: no manifest, the release was installed by an older jorup");
+            return Ok(());
+        }
+    };
+    println!("source:       {}", manifest.source());
+    if let Some(asset) = manifest.asset() {
+        println!("asset:        {}", asset);
     }
+    if let Some(sha256) = manifest.sha256() {
+        println!("sha256:       {}", sha256);
+    }
+    if let Some(size) = manifest.size() {
+        println!("size:         {} bytes", size);
+    }
+    println!("installed at: {}", manifest.installed_at().to_rfc3339());
+    println!("target:       {}", manifest.target());
+    println!(
+        "jormungandr:  {}",
+        manifest.jormungandr_version().unwrap_or("unknown")
+    );
+    println!(
+        "jcli:         {}",
+        manifest.jcli_version().unwrap_or("unknown")
+    );
+
     Ok(())
 }
 
-fn remove(cfg: JorupConfig, version: Version) -> Result<(), Error> {
-    let version_req = VersionReq::exact(version);
-    let release = Release::load(&cfg, &version_req).map_err(Error::ReleaseLoad)?;
-    std::fs::remove_dir_all(release.dir()).map_err(Error::RemoveRelease)?;
+fn verify(cfg: JorupConfig, version: Option<Version>, upstream: bool) -> Result<(), Error> {
+    let releases = match version {
+        Some(version) => vec![Ok(
+            Release::load(&cfg, &VersionReq::exact(version)).map_err(Error::ReleaseLoad)?
+        )],
+        None => release::list_release_dirs(&cfg).map_err(Error::ReleasesList)?,
+    };
+    let mut client = if upstream {
+        Some(cfg.client().map_err(Error::DownloaderCreate)?)
+    } else {
+        None
+    };
+
+    let mut problems = 0;
+    for release in releases {
+        let release = match release {
+            Ok(release) => release,
+            Err(err) => {
+                println!("**** invalid entry: 1 problem(s)");
+                problems += 1;
+                crate::utils::print_error(err);
+                continue;
+            }
+        };
+        let mut found = Vec::new();
+        let upstream_sha256 = match client.as_mut() {
+            Some(client) => match upstream_checksum(&cfg, client, &release) {
+                Ok(sha256) => sha256,
+                Err(err) => {
+                    found.push(err);
+                    None
+                }
+            },
+            None => None,
+        };
+        found.extend(release.verify(upstream_sha256.as_deref()));
+
+        if found.is_empty() {
+            println!("**** {}: ok", release.version());
+        } else {
+            println!("**** {}: {} problem(s)", release.version(), found.len());
+            problems += found.len();
+            for problem in found {
+                crate::utils::print_error(problem);
+            }
+        }
+    }
+
+    if let Some(default) = Release::current_default(&cfg) {
+        let mut broken = Vec::new();
+        if !default.dir().is_dir() {
+            broken.push(format!(
+                "the default release {} is not installed",
+                default.version()
+            ));
+        }
+        for proxy in proxy::broken(&cfg) {
+            broken.push(format!("{} is missing or a dangling link", proxy.display()));
+        }
+        if !broken.is_empty() {
+            println!("**** default: {} problem(s)", broken.len());
+            problems += broken.len();
+            for problem in broken {
+                eprintln!("{}", problem);
+            }
+            eprintln!("HINT: run `jorup node default <version>` to restore the default release");
+        }
+    }
+
+    if problems == 0 {
+        Ok(())
+    } else {
+        Err(Error::Unhealthy(problems))
+    }
+}
+
+/// the checksum published for the archive a release was installed from
+fn upstream_checksum(
+    cfg: &JorupConfig,
+    client: &mut download::Client,
+    release: &Release,
+) -> Result<Option<String>, ReleaseError> {
+    let target = release
+        .manifest()?
+        .and_then(|manifest| manifest.target().parse().ok())
+        .unwrap_or_else(Target::current);
+    let asset = release.asset_remote(cfg.release_source(), client, &target)?;
+    Ok(asset.sha256().map(str::to_owned))
+}
+
+fn prune(
+    mut cfg: JorupConfig,
+    keep_nightlies: Option<usize>,
+    keep_compatible: bool,
+    unused_days: Option<u32>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    if keep_nightlies.is_none() && !keep_compatible && unused_days.is_none() {
+        return Err(Error::NoPrunePolicy);
+    }
+
+    let mut releases = list_installed_releases(&cfg).map_err(Error::ReleasesList)?;
+    releases.sort_by(|a, b| b.version().cmp(a.version()));
+
+    let compatible: Vec<VersionReq> = if keep_compatible {
+        cfg.load_jor()
+            .map_err(Error::Jorfile)?
+            .blockchains()
+            .iter()
+            .map(|blockchain| blockchain.jormungandr_versions().clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let default = Release::current_default(&cfg).map(|release| release.version().clone());
+    let running = runner::running_nodes(&cfg).map_err(Error::Runner)?;
+
+    let old_nightlies: Vec<Version> = match keep_nightlies {
+        Some(keep) => releases
+            .iter()
+            .map(Release::version)
+            .filter(|version| version.channel() == Channel::Nightly)
+            .skip(keep)
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+    for release in releases {
+        let version = release.version();
+        let reason = if old_nightlies.contains(version) {
+            Some("older nightly".to_owned())
+        } else if keep_compatible && !compatible.iter().any(|req| req.matches(version)) {
+            Some("not compatible with any blockchain".to_owned())
+        } else {
+            unused_days.and_then(|days| {
+                let manifest = release.manifest().ok().flatten()?;
+                let unused = (chrono::Utc::now() - *manifest.last_used()).num_days();
+                if unused >= i64::from(days) {
+                    Some(format!("unused for {} days", unused))
+                } else {
+                    None
+                }
+            })
+        };
+        let reason = match reason {
+            Some(reason) => reason,
+            None => continue,
+        };
+
+        if default.as_ref() == Some(version) {
+            println!("**** keeping {}: default release", version);
+            continue;
+        }
+        if let Some((blockchain, _)) = running.iter().find(|(_, info)| info.uses(release.dir())) {
+            println!(
+                "**** keeping {}: used by the running {} node",
+                version, blockchain
+            );
+            continue;
+        }
+
+        let size = release.disk_usage();
+        if dry_run {
+            println!(
+                "**** would remove {} ({}): {}",
+                version,
+                HumanBytes(size),
+                reason
+            );
+        } else {
+            std::fs::remove_dir_all(release.dir()).map_err(Error::RemoveRelease)?;
+            println!(
+                "**** removed {} ({}): {}",
+                version,
+                HumanBytes(size),
+                reason
+            );
+        }
+        removed += 1;
+        reclaimed += size;
+    }
+
+    if dry_run {
+        println!(
+            "**** {} release(s) would be removed, reclaiming {}",
+            removed,
+            HumanBytes(reclaimed)
+        );
+    } else {
+        println!(
+            "**** {} release(s) removed, {} reclaimed",
+            removed,
+            HumanBytes(reclaimed)
+        );
+    }
+
+    Ok(())
+}
+
+fn remove(mut cfg: JorupConfig, version_req: VersionReq, force: bool) -> Result<(), Error> {
+    let (removed, kept): (Vec<Release>, Vec<Release>) = list_installed_releases(&cfg)
+        .map_err(Error::ReleasesList)?
+        .into_iter()
+        .partition(|release| version_req.matches(release.version()));
+    if removed.is_empty() {
+        return Err(Error::ReleaseLoad(
+            ReleaseError::NoCompatibleReleaseInstalled(version_req),
+        ));
+    }
+
+    let default = Release::current_default(&cfg)
+        .filter(|default| removed.iter().any(|r| r.version() == default.version()));
+    let running: Vec<(String, runner::RunnerInfo)> = runner::running_nodes(&cfg)
+        .map_err(Error::Runner)?
+        .into_iter()
+        .filter(|(_, info)| removed.iter().any(|release| info.uses(release.dir())))
+        .collect();
+
+    if !force && (default.is_some() || !running.is_empty()) {
+        if let Some(default) = &default {
+            eprintln!("{} is the default release", default.version());
+        }
+        for (blockchain, info) in &running {
+            eprintln!(
+                "the {} node (pid {}) runs {}",
+                blockchain,
+                info.pid(),
+                info.jormungandr().display()
+            );
+        }
+        return Err(Error::ReleasesInUse);
+    }
+
+    for (blockchain, _) in running {
+        stop_node(&mut cfg, &blockchain).map_err(|e| Error::Shutdown(blockchain.clone(), e))?;
+        println!("**** {} node stopped", blockchain);
+    }
+
+    for release in &removed {
+        std::fs::remove_dir_all(release.dir()).map_err(Error::RemoveRelease)?;
+        println!("**** release {} removed", release.version());
+    }
+
+    if let Some(default) = default {
+        // prefer a release of the same channel as the removed default, then
+        // a stable one
+        let latest_of = |channel: Channel| {
+            kept.iter()
+                .filter(|release| release.version().channel() == channel)
+                .max_by_key(|release| release.version())
+        };
+        let next = latest_of(default.version().channel())
+            .or_else(|| latest_of(Channel::Stable))
+            .or_else(|| kept.iter().max_by_key(|release| release.version()));
+        match next {
+            Some(release) => {
+                release.make_default(&cfg).map_err(Error::ReleaseLoad)?;
+                println!("**** {} is now the default release", release.version());
+            }
+            None => {
+                let _ = std::fs::remove_file(cfg.default_release_file());
+                eprintln!("WARN: no release left to make the default");
+                eprintln!("HINT: run `jorup node install --make-default`");
+            }
+        }
+    }
+
+    Ok(())
+}
 
+fn stop_node(cfg: &mut JorupConfig, blockchain: &str) -> Result<(), ShutdownError> {
+    let blockchain = Blockchain::load(cfg, blockchain)?;
+    runner::RunnerControl::load(&blockchain)?.shutdown()?;
     Ok(())
 }
@@ -1,6 +1,8 @@
 use crate::{
     common::JorupConfig,
-    utils::{blockchain::Blockchain, release::Release, runner::RunnerControl, version::VersionReq},
+    utils::{
+        blockchain::Blockchain, pin, release::Release, runner::RunnerControl, version::VersionReq,
+    },
 };
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -12,8 +14,11 @@ use thiserror::Error;
 /// Run the jormungandr
 #[derive(Debug, StructOpt)]
 pub struct Command {
-    /// The blockchain to run jormungandr for
-    blockchain: String,
+    /// The blockchain to run jormungandr for. If not specified, the
+    /// blockchain pinned in a `.jorup-version` or `jorup.toml` file of the
+    /// current directory or its parents is used, along with the version and
+    /// REST address it pins.
+    blockchain: Option<String>,
 
     /// The version of Jormungandr to run. If not specified, the latest
     /// compatible version will be used.
@@ -55,6 +60,8 @@ pub struct Command {
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("Cannot determine the blockchain to run")]
+    Pin(#[source] pin::Error),
     #[error("Cannot run the node without valid blockchain")]
     NoValidBlockchain(#[source] crate::utils::blockchain::Error),
     #[error("Cannot run without compatible release")]
@@ -73,16 +80,21 @@ pub enum Error {
 
 impl Command {
     pub fn run(self, mut cfg: JorupConfig) -> Result<(), Error> {
+        let (blockchain_name, pin) = pin::select_blockchain(self.blockchain).map_err(Error::Pin)?;
+        let version_req = self
+            .version_req
+            .or_else(|| pin.as_ref().and_then(|pin| pin.version_req().cloned()));
+
         // prepare entry directory
         let blockchain =
-            Blockchain::load(&mut cfg, &self.blockchain).map_err(Error::NoValidBlockchain)?;
+            Blockchain::load(&mut cfg, &blockchain_name).map_err(Error::NoValidBlockchain)?;
         blockchain.prepare().map_err(Error::NoValidBlockchain)?;
 
         let bin = if let Some(dir) = self.bin {
             eprintln!("WARN: using custom binaries from {}", dir.display());
             std::fs::canonicalize(dir).map_err(Error::Canonicalize)?
         } else {
-            let release = if let Some(version_req) = self.version_req {
+            let release = if let Some(version_req) = version_req {
                 Release::load(&cfg, &version_req)
             } else {
                 Release::load(&cfg, blockchain.jormungandr_version_req())
@@ -92,11 +104,12 @@ impl Command {
                 Error::NoCompatibleRelease(err)
             })?;
 
-            if release.asset_need_fetched() {
-                // asset release is not available
+            if release.asset_need_open() {
+                // release binaries are not available
                 return Err(Error::NoCompatibleBinaries);
             }
 
+            release.mark_used();
             release.dir().clone()
         };
 
@@ -117,7 +130,10 @@ impl Command {
             extra
         };
 
-        let rest_addr = match self.rest_listen {
+        let rest_listen = self
+            .rest_listen
+            .or_else(|| pin.as_ref().and_then(pin::Pin::rest_listen));
+        let rest_addr = match rest_listen {
             Some(addr) => Some(addr),
             None => match &self.config {
                 Some(config) => crate::jormungandr_config::load_config(config)
@@ -1,5 +1,13 @@
 use super::Cmd;
-use crate::{common::JorupConfig, utils::download};
+use crate::{
+    common::JorupConfig,
+    utils::{
+        download, proxy,
+        release::{fetch_text, Release},
+        signature::{self, TrustedKeys},
+        target::{AssetKind, Target},
+    },
+};
 use std::{
     env::{self, consts::EXE_SUFFIX},
     fs, io,
@@ -12,7 +20,16 @@ use thiserror::Error;
 #[derive(Debug, StructOpt)]
 pub enum Command {
     Install(Install),
-    Update,
+    Update {
+        /// Install the update even if it is not signed by a trusted key
+        #[structopt(long)]
+        allow_unsigned: bool,
+
+        /// Install the jorup build for the given target triple instead of
+        /// the one jorup was built for
+        #[structopt(long)]
+        target: Option<Target>,
+    },
     Uninstall,
 }
 
@@ -55,15 +72,29 @@ pub enum Error {
     UpdateCheck(#[from] crate::utils::jorup_update::Error),
     #[error("Failed to download an update")]
     UpdateDownload(#[from] download::Error),
-    #[error("Could not find the latest jorup binary for the current platform")]
-    UpdateAssetNotFound,
+    #[error("Could not find the latest jorup binary for {target}, available assets: {}", .candidates.join(", "))]
+    UpdateAssetNotFound {
+        target: String,
+        candidates: Vec<String>,
+    },
+    #[error("Cannot load the trusted public keys")]
+    TrustedKeys(#[source] signature::Error),
+    #[error("Refusing to install the update: no signature from a trusted key, use `--allow-unsigned` to install anyway")]
+    UpdateUnsigned,
+    #[error("The update is not correctly signed")]
+    UpdateBadSignature(#[source] signature::Error),
+    #[error("Cannot lock the jorup home")]
+    Lock(#[from] crate::utils::lock::Error),
 }
 
 impl Command {
     pub fn run(self, cfg: JorupConfig) -> Result<(), Error> {
         match self {
             Command::Install(cmd) => cmd.run(cfg),
-            Command::Update => update(cfg),
+            Command::Update {
+                allow_unsigned,
+                target,
+            } => update(cfg, allow_unsigned, target.unwrap_or_else(Target::current)),
             Command::Uninstall => uninstall(cfg),
         }
     }
@@ -71,6 +102,7 @@ impl Command {
 
 impl Install {
     pub fn run(self, cfg: JorupConfig) -> Result<(), Error> {
+        let _lock = cfg.lock()?;
         let bin_dir = cfg.bin_dir();
         let jorup_file = bin_dir.join(format!("jorup{}", EXE_SUFFIX));
 
@@ -102,7 +134,8 @@ impl Cmd for Install {
     type Err = Error;
 
     fn run(self) -> Result<(), Self::Err> {
-        let cfg = crate::common::JorupConfig::new(None, None, false)?;
+        let cfg = crate::common::JorupConfig::new(None, None, false, None)?;
+        cfg.detect_installed_path();
         self.run(cfg)
     }
 }
@@ -111,11 +144,12 @@ pub fn uninstall(_cfg: JorupConfig) -> Result<(), Error> {
     unimplemented!()
 }
 
-pub fn update(cfg: JorupConfig) -> Result<(), Error> {
+pub fn update(cfg: JorupConfig, allow_unsigned: bool, target: Target) -> Result<(), Error> {
+    let _lock = cfg.lock()?;
     let bin_dir = cfg.bin_dir();
     let jorup_file = bin_dir.join(format!("jorup{}", EXE_SUFFIX));
 
-    match crate::utils::check_jorup_update()? {
+    match crate::utils::check_jorup_update(&cfg)? {
         Some(release) => {
             let perform_update = dialoguer::Confirmation::new()
                 .with_text(&format!(
@@ -129,11 +163,54 @@ pub fn update(cfg: JorupConfig) -> Result<(), Error> {
                 return Ok(());
             }
 
-            let mut client = download::Client::new()?;
-            let url = release
-                .get_asset_url(env!("TARGET"))
-                .ok_or(Error::UpdateAssetNotFound)?;
-            client.download_file("jorup", url, jorup_file)?;
+            let mut client = cfg.client()?;
+            let asset = release
+                .get_asset(&target, AssetKind::Binary)
+                .ok_or_else(|| Error::UpdateAssetNotFound {
+                    target: target.to_string(),
+                    candidates: release.asset_names(),
+                })?;
+
+            let keys = TrustedKeys::load(&cfg).map_err(Error::TrustedKeys)?;
+            let signature = match release.get_signature_asset(asset) {
+                Some(signature_asset) if !keys.is_empty() => {
+                    Some(fetch_text(&mut client, signature_asset)?)
+                }
+                _ => None,
+            };
+            if keys.is_empty() {
+                eprintln!(
+                    "WARN: no trusted signing key configured, the signature of the update is not checked"
+                );
+            } else if signature.is_none() {
+                if !allow_unsigned {
+                    return Err(Error::UpdateUnsigned);
+                }
+                eprintln!("WARN: the update is not signed by a trusted key, installing anyway");
+            }
+
+            // download next to the current executable so it is only replaced
+            // once the new binary has been verified
+            let download_file = bin_dir.join(format!("jorup.download{}", EXE_SUFFIX));
+            client.download_file("jorup", asset.url(), &download_file)?;
+            if let Some(signature) = signature {
+                if let Err(err) = keys.verify(&download_file, &signature) {
+                    let _ = fs::remove_file(&download_file);
+                    return Err(Error::UpdateBadSignature(err));
+                }
+            }
+            make_executable(&download_file)?;
+            fs::rename(&download_file, &jorup_file)
+                .map_err(|e| Error::Install(e, jorup_file.clone()))?;
+            // the proxies are links to the previous executable. Before the
+            // proxies, the default release was only known from the links to
+            // its binaries they replace, record it first
+            if let Some(default) = Release::current_default(&cfg) {
+                default
+                    .record_default(&cfg)
+                    .map_err(|e| Error::Install(e, cfg.default_release_file()))?;
+                proxy::install(&cfg).map_err(|e| Error::Install(e, bin_dir.clone()))?;
+            }
             eprintln!("Jorup was successfully updated!");
         }
         None => {
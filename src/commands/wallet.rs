@@ -1,6 +1,6 @@
 use crate::{
     common::JorupConfig,
-    utils::{blockchain::Blockchain, jcli::Jcli, release::Release, version::VersionReq},
+    utils::{blockchain::Blockchain, jcli::Jcli, pin, release::Release, version::VersionReq},
 };
 use serde::Serialize;
 use std::path::PathBuf;
@@ -11,8 +11,10 @@ use thiserror::Error;
 /// secret key path.
 #[derive(Debug, StructOpt)]
 pub struct Command {
-    /// The blockchain to run jormungandr for
-    blockchain: String,
+    /// The blockchain to run jormungandr for. If not specified, the
+    /// blockchain and version pinned in a `.jorup-version` or `jorup.toml`
+    /// file of the current directory or its parents are used
+    blockchain: Option<String>,
 
     /// Address prefix (ignored by node, exists for readability, default: jorup_)
     #[structopt(default_value = "jorup_")]
@@ -41,6 +43,8 @@ pub struct Command {
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("Cannot determine the blockchain to use")]
+    Pin(#[source] pin::Error),
     #[error("Cannot run the node without valid blockchain")]
     NoValidBlockchain(#[source] crate::utils::blockchain::Error),
     #[error("Cannot run without compatible release")]
@@ -73,16 +77,21 @@ struct BftSecret {
 
 impl Command {
     pub fn run(self, mut cfg: JorupConfig) -> Result<(), Error> {
+        let (blockchain_name, pin) = pin::select_blockchain(self.blockchain).map_err(Error::Pin)?;
+        let version_req = self
+            .version_req
+            .or_else(|| pin.as_ref().and_then(|pin| pin.version_req().cloned()));
+
         // prepare entry directory
         let blockchain =
-            Blockchain::load(&mut cfg, &self.blockchain).map_err(Error::NoValidBlockchain)?;
+            Blockchain::load(&mut cfg, &blockchain_name).map_err(Error::NoValidBlockchain)?;
         blockchain.prepare().map_err(Error::NoValidBlockchain)?;
 
         let bin = if let Some(dir) = self.bin {
             eprintln!("WARN: using custom binaries from {}", dir.display());
             dir.join("jcli")
         } else {
-            let release = if let Some(version_req) = self.version_req {
+            let release = if let Some(version_req) = version_req {
                 Release::load(&cfg, &version_req)
             } else {
                 Release::load(&cfg, blockchain.jormungandr_version_req())
@@ -92,8 +101,8 @@ impl Command {
                 Error::NoCompatibleRelease(err)
             })?;
 
-            if release.asset_need_fetched() {
-                // asset release is not available
+            if release.asset_need_open() {
+                // release binaries are not available
                 return Err(Error::NoCompatibleBinaries);
             }
 
@@ -1,7 +1,22 @@
-use crate::utils::download::Client;
-use std::{collections::BTreeSet, io, path::PathBuf};
+use crate::{
+    settings::Settings,
+    utils::{
+        download::Client,
+        lock::{self, Lock},
+        registry::{self, Registries, Registry},
+        release_source::{self, ReleaseSource},
+    },
+};
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
+/// the origin of the blockchains defined with `jorup blockchain add`
+pub const LOCAL: &str = "local";
+
 #[derive(Debug)]
 pub struct JorupConfig {
     home_dir: PathBuf,
@@ -9,6 +24,8 @@ pub struct JorupConfig {
     jor_file: Option<PathBuf>,
     jor: Option<crate::config::Config>,
     offline: bool,
+    settings: Settings,
+    release_source: Box<dyn ReleaseSource>,
 }
 
 #[derive(Debug, Error)]
@@ -23,8 +40,16 @@ pub enum Error {
     CannotOpenFile(#[source] io::Error, PathBuf),
     #[error("Cannot parse file: {1}")]
     Json(#[source] serde_json::Error, PathBuf),
-    #[error("Cannot sync jorfile with registry")]
-    CannotSyncRegistry(#[source] crate::utils::download::Error),
+    #[error("Cannot write file: {1}")]
+    CannotWriteFile(#[source] io::Error, PathBuf),
+    #[error("Cannot sync the jorfile of the {0} registry")]
+    CannotSyncRegistry(String, #[source] crate::utils::download::Error),
+    #[error("Cannot sync the jorfiles of the registries: {}", .0.join(", "))]
+    CannotSyncRegistries(Vec<String>),
+    #[error("Cannot load the jorfile registries")]
+    Registry(#[source] registry::Error),
+    #[error("Invalid release source")]
+    InvalidReleaseSource(#[source] release_source::Error),
 }
 
 impl JorupConfig {
@@ -32,6 +57,7 @@ impl JorupConfig {
         jorup_home: Option<PathBuf>,
         jorfile: Option<PathBuf>,
         offline: bool,
+        release_source: Option<String>,
     ) -> Result<Self, Error> {
         let home_dir = jorup_home
             .or_else(|| dirs::home_dir().map(|d| d.join(".jorup")))
@@ -46,15 +72,25 @@ impl JorupConfig {
         std::fs::create_dir_all(&home_dir)
             .map_err(|e| Error::CannotCreateHomeDir(e, home_dir.clone()))?;
 
+        let settings = load_settings(home_dir.join("settings.json"))?;
+
+        let release_source = release_source
+            .as_deref()
+            .or_else(|| settings.release_source())
+            .unwrap_or("github");
+        let release_source =
+            release_source::from_spec(release_source).map_err(Error::InvalidReleaseSource)?;
+
         let cfg = JorupConfig {
             home_dir,
             jor_file: jorfile,
             jor: None,
             offline,
+            settings,
+            release_source,
         };
 
         cfg.init()?;
-        cfg.detect_installed_path();
 
         Ok(cfg)
     }
@@ -66,11 +102,15 @@ impl JorupConfig {
             .map_err(|e| Error::CannotCreateInitDir(e, self.blockchain_dir()))?;
         std::fs::create_dir_all(self.release_dir())
             .map_err(|e| Error::CannotCreateInitDir(e, self.release_dir()))?;
+        std::fs::create_dir_all(self.cache_dir())
+            .map_err(|e| Error::CannotCreateInitDir(e, self.cache_dir()))?;
 
         Ok(())
     }
 
-    fn detect_installed_path(&self) {
+    /// warn when `$JORUP_HOME/bin` is not in the PATH or other installations
+    /// shadow it
+    pub fn detect_installed_path(&self) {
         let bin_dir = if self.bin_dir().is_absolute() {
             self.bin_dir()
         } else {
@@ -118,37 +158,170 @@ impl JorupConfig {
         self.home_dir.join("release")
     }
 
+    pub fn source_dir(&self) -> PathBuf {
+        self.home_dir.join("src")
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.home_dir.join("cache")
+    }
+
+    /// the file recording the release run by the `jormungandr` and `jcli`
+    /// proxies when no other version is selected
+    pub fn default_release_file(&self) -> PathBuf {
+        self.home_dir.join("default-release")
+    }
+
+    /// take the lock serialising the jorup invocations changing the
+    /// installed releases, the proxies or the jorfile
+    pub fn lock(&self) -> Result<Lock, lock::Error> {
+        Lock::acquire(&self.home_dir.join("jorup.lock"), "the jorup home")
+    }
+
     pub fn offline(&self) -> bool {
         self.offline
     }
 
+    /// a download client caching release metadata, serving them from the
+    /// cache only when `--offline`
+    pub fn client(&self) -> Result<Client, crate::utils::download::Error> {
+        Ok(Client::new()?.with_cache(self.cache_dir(), self.offline))
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn release_source(&self) -> &dyn ReleaseSource {
+        self.release_source.as_ref()
+    }
+
+    /// the jorfiles added with `jorup blockchain source add`
+    pub fn registries_file(&self) -> PathBuf {
+        self.home_dir.join("registries.json")
+    }
+
+    pub fn registries(&self) -> Result<Registries, Error> {
+        Registries::load(&self.registries_file()).map_err(Error::Registry)
+    }
+
+    /// sync the upstream jorfile and the registries, carrying on past the
+    /// ones that fail so one unreachable registry does not hold back the
+    /// others
     pub fn sync_jorfile(&self, client: &mut Client) -> Result<(), Error> {
+        let mut failed = Vec::new();
+
         // do not sync if the jorfile was given as parameter of the
         // command line or if `--offline`
-        if self.jor_file.is_some() || self.offline {
-            return Ok(());
+        if self.jor_file.is_none() && !self.offline {
+            if let Err(err) =
+                client.download_file("jorfile", registry::UPSTREAM_URL, self.jorfile())
+            {
+                eprintln!("WARN: the {} registry was not synced", registry::UPSTREAM);
+                crate::utils::print_error(err);
+                failed.push(registry::UPSTREAM.to_owned());
+            }
+        }
+
+        for registry in self.registries()?.iter() {
+            if self.offline && !registry.is_local() {
+                continue;
+            }
+            if let Err(err) = self.sync_registry(client, registry) {
+                eprintln!("WARN: the {} registry was not synced", registry.name());
+                crate::utils::print_error(err);
+                failed.push(registry.name().to_owned());
+            }
         }
 
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::CannotSyncRegistries(failed))
+        }
+    }
+
+    /// fetch the jorfile of a registry into its cache
+    pub fn sync_registry(&self, client: &mut Client, registry: &Registry) -> Result<(), Error> {
+        let file = registry.cache_file(&self.cache_dir());
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| Error::CannotCreateInitDir(e, dir.to_path_buf()))?;
+        }
         client
-            .download_file(
-                "jorfile",
-                "https://raw.githubusercontent.com/input-output-hk/jorup/master/jorfile.json",
-                self.jorfile(),
-            )
-            .map_err(Error::CannotSyncRegistry)
+            .download_file(registry.name(), registry.url(), &file)
+            .map_err(|e| Error::CannotSyncRegistry(registry.name().to_owned(), e))?;
+        read_jorfile(&file).map(|_| ())
     }
 
+    /// the blockchains defined with `jorup blockchain add`
+    pub fn local_blockchains_file(&self) -> PathBuf {
+        self.home_dir.join("blockchains.json")
+    }
+
+    pub fn local_blockchains(&self) -> Result<crate::config::Config, Error> {
+        let path = self.local_blockchains_file();
+        if !path.is_file() {
+            return Ok(crate::config::Config::default());
+        }
+        read_jorfile(&path)
+    }
+
+    pub fn save_local_blockchains(
+        &mut self,
+        blockchains: &crate::config::Config,
+    ) -> Result<(), Error> {
+        let path = self.local_blockchains_file();
+        let content = serde_json::to_vec_pretty(blockchains).unwrap();
+        std::fs::write(&path, content).map_err(|e| Error::CannotWriteFile(e, path))?;
+        self.jor = None;
+        Ok(())
+    }
+
+    /// the blockchains defined locally and the ones of the registries merged
+    /// with the upstream jorfile. Local definitions take precedence, then a
+    /// registry over the ones listed after it and over the upstream jorfile
     pub fn load_jor(&mut self) -> Result<&crate::config::Config, Error> {
         if self.jor.is_none() {
-            let file = std::fs::File::open(self.jorfile()).map_err(|e| {
-                eprintln!("HINT: run `jorup blockchain update`");
-                Error::CannotOpenFile(e, self.jorfile())
-            })?;
+            let mut jor = crate::config::Config::default();
+            jor.merge(self.local_blockchains()?, LOCAL);
+            for registry in self.registries()?.iter() {
+                let file = registry.cache_file(&self.cache_dir());
+                if !file.is_file() {
+                    eprintln!("WARN: the {} registry was not synced", registry.name());
+                    eprintln!("HINT: run `jorup blockchain update`");
+                    continue;
+                }
+                jor.merge(read_jorfile(&file)?, registry.name());
+            }
 
-            let jor = serde_json::from_reader(file).map_err(|e| Error::Json(e, self.jorfile()))?;
+            match read_jorfile(&self.jorfile()) {
+                Ok(upstream) => jor.merge(upstream, registry::UPSTREAM),
+                // local blockchains and registries may be all that is used
+                Err(Error::CannotOpenFile(..)) if !jor.blockchains().is_empty() => (),
+                Err(err) => {
+                    eprintln!("HINT: run `jorup blockchain update`");
+                    return Err(err);
+                }
+            }
             self.jor = Some(jor);
         }
 
         Ok(self.jor.as_ref().unwrap())
     }
 }
+
+fn read_jorfile(path: &Path) -> Result<crate::config::Config, Error> {
+    let file =
+        std::fs::File::open(path).map_err(|e| Error::CannotOpenFile(e, path.to_path_buf()))?;
+    serde_json::from_reader(file).map_err(|e| Error::Json(e, path.to_path_buf()))
+}
+
+fn load_settings(path: PathBuf) -> Result<Settings, Error> {
+    if !path.is_file() {
+        return Ok(Settings::default());
+    }
+
+    let file = std::fs::File::open(&path).map_err(|e| Error::CannotOpenFile(e, path.clone()))?;
+    serde_json::from_reader(file).map_err(|e| Error::Json(e, path))
+}
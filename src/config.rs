@@ -1,16 +1,27 @@
 use crate::utils::version::VersionReq;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use thiserror::Error;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config(Vec<Blockchain>);
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Blockchain {
     name: String,
     description: String,
     jormungandr_versions: VersionReq,
     block0_hash: String,
     trusted_peers: Vec<TrustedPeer>,
+    /// SHA-256 digests of jormungandr release assets, indexed by asset name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    asset_checksums: BTreeMap<String, String>,
+    /// the registry the entry comes from, set when the jorfiles are merged
+    #[serde(skip)]
+    origin: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +29,23 @@ pub struct TrustedPeer {
     address: String,
 }
 
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid blockchain name {0}, expecting letters, digits, `-` or `_`")]
+    InvalidName(String),
+    #[error("Invalid genesis block hash {0}, expecting 64 hexadecimal characters")]
+    InvalidBlock0Hash(String),
+    #[error("Invalid trusted peer {0}, expecting an address such as /ip4/1.2.3.4/tcp/3000")]
+    InvalidPeer(String),
+}
+
+/// whether `name` can name a blockchain or a registry: it is used as a
+/// directory or file name in the jorup home
+pub fn is_valid_name(name: &str) -> bool {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    !name.is_empty() && name.chars().all(valid)
+}
+
 impl Config {
     pub fn get_blockchain(&self, name: &str) -> Option<&Blockchain> {
         self.0.iter().find(|blockchain| blockchain.name() == name)
@@ -26,9 +54,103 @@ impl Config {
     pub fn blockchains(&self) -> &[Blockchain] {
         &self.0
     }
+
+    /// add or replace a blockchain
+    pub fn set(&mut self, blockchain: Blockchain) {
+        match self.0.iter_mut().find(|b| b.name == blockchain.name) {
+            Some(entry) => *entry = blockchain,
+            None => self.0.push(blockchain),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Blockchain> {
+        let index = self
+            .0
+            .iter()
+            .position(|blockchain| blockchain.name == name)?;
+        Some(self.0.remove(index))
+    }
+
+    /// add the blockchains of a jorfile with a lower priority, the entries
+    /// already present take precedence
+    ///
+    /// Entries with an invalid name are skipped: jorfiles may come from
+    /// third parties and the name is used as a directory name.
+    pub fn merge(&mut self, other: Config, origin: &str) {
+        for mut blockchain in other.0 {
+            if !is_valid_name(blockchain.name()) {
+                eprintln!(
+                    "WARN: ignoring the blockchain {:?} of the {} jorfile, invalid name",
+                    blockchain.name(),
+                    origin
+                );
+            } else if self.get_blockchain(blockchain.name()).is_none() {
+                blockchain.origin = origin.to_owned();
+                self.0.push(blockchain);
+            }
+        }
+    }
 }
 
 impl Blockchain {
+    /// a blockchain entry, checking the name, the genesis block hash and
+    /// the addresses of the trusted peers
+    pub fn new(
+        name: String,
+        description: String,
+        jormungandr_versions: VersionReq,
+        block0_hash: String,
+        trusted_peers: Vec<String>,
+    ) -> Result<Self, Error> {
+        if !is_valid_name(&name) {
+            return Err(Error::InvalidName(name));
+        }
+        let blockchain = Blockchain {
+            name,
+            description,
+            jormungandr_versions,
+            block0_hash: String::new(),
+            trusted_peers: Vec::new(),
+            asset_checksums: BTreeMap::new(),
+            origin: String::new(),
+        };
+        blockchain
+            .with_block0_hash(block0_hash)?
+            .with_trusted_peers(trusted_peers)
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn with_jormungandr_versions(mut self, jormungandr_versions: VersionReq) -> Self {
+        self.jormungandr_versions = jormungandr_versions;
+        self
+    }
+
+    pub fn with_block0_hash(mut self, block0_hash: String) -> Result<Self, Error> {
+        if block0_hash.len() != 64 || !block0_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidBlock0Hash(block0_hash));
+        }
+        self.block0_hash = block0_hash.to_ascii_lowercase();
+        Ok(self)
+    }
+
+    pub fn with_trusted_peers(mut self, trusted_peers: Vec<String>) -> Result<Self, Error> {
+        self.trusted_peers = trusted_peers
+            .into_iter()
+            .map(|address| {
+                if is_peer_address(&address) {
+                    Ok(TrustedPeer { address })
+                } else {
+                    Err(Error::InvalidPeer(address))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -48,6 +170,15 @@ impl Blockchain {
     pub fn trusted_peers(&self) -> &[TrustedPeer] {
         &self.trusted_peers
     }
+
+    /// the name of the registry the entry comes from
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn asset_checksum(&self, asset_name: &str) -> Option<&str> {
+        self.asset_checksums.get(asset_name).map(String::as_str)
+    }
 }
 
 impl TrustedPeer {
@@ -55,3 +186,166 @@ impl TrustedPeer {
         &self.address
     }
 }
+
+/// whether the address is a multiaddr jormungandr can connect to: an IP
+/// address or a DNS name followed by a TCP port, optionally followed by the
+/// peer id (`/ip4/1.2.3.4/tcp/3000`, `/dns4/peer.example.com/tcp/3000/p2p/<id>`)
+fn is_peer_address(address: &str) -> bool {
+    let mut components = match address.strip_prefix('/') {
+        Some(rest) => rest.split('/'),
+        None => return false,
+    };
+
+    let host = match (components.next(), components.next()) {
+        (Some("ip4"), Some(ip)) => ip.parse::<Ipv4Addr>().is_ok(),
+        (Some("ip6"), Some(ip)) => ip.parse::<Ipv6Addr>().is_ok(),
+        (Some("dns"), Some(name)) | (Some("dns4"), Some(name)) | (Some("dns6"), Some(name)) => {
+            is_dns_name(name)
+        }
+        _ => false,
+    };
+    let port = match (components.next(), components.next()) {
+        (Some("tcp"), Some(port)) => port.parse::<u16>().is_ok_and(|port| port != 0),
+        _ => false,
+    };
+    let peer_id = match (components.next(), components.next()) {
+        (None, None) => true,
+        (Some("p2p"), Some(id)) | (Some("ipfs"), Some(id)) => {
+            !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+        }
+        _ => false,
+    };
+
+    host && port && peer_id && components.next().is_none()
+}
+
+fn is_dns_name(name: &str) -> bool {
+    name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jorfile(names: &[&str]) -> Config {
+        let blockchains: Vec<_> = names
+            .iter()
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "description": "",
+                    "jormungandr_versions": "^0.9",
+                    "block0_hash": "",
+                    "trusted_peers": [],
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::Value::Array(blockchains)).unwrap()
+    }
+
+    #[test]
+    fn merge_skips_invalid_names() {
+        let mut config = Config::default();
+        config.merge(
+            jorfile(&["testnet", "../../outside", "", "with space", "/abs"]),
+            "private",
+        );
+        let names: Vec<&str> = config.blockchains().iter().map(Blockchain::name).collect();
+        assert_eq!(names, ["testnet"]);
+    }
+
+    const HASH: &str = "adbdd5ede31637f6c9bad5c271eec0bc3d0cb9efb86a5b913bb55cba549d0770";
+
+    fn blockchain(name: &str, block0_hash: &str, peers: &[&str]) -> Result<Blockchain, Error> {
+        Blockchain::new(
+            name.to_owned(),
+            String::new(),
+            VersionReq::parse("^0.9").unwrap(),
+            block0_hash.to_owned(),
+            peers.iter().map(|peer| (*peer).to_owned()).collect(),
+        )
+    }
+
+    #[test]
+    fn names() {
+        for name in &["itn", "private-testnet_2"] {
+            assert!(is_valid_name(name), "{}", name);
+        }
+        for name in &["", "../itn", "itn/2", "with space", "testnet.json"] {
+            assert!(!is_valid_name(name), "{}", name);
+            assert!(matches!(
+                blockchain(name, HASH, &[]),
+                Err(Error::InvalidName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn block0_hashes() {
+        let upper = HASH.to_ascii_uppercase();
+        let itn = blockchain("itn", &upper, &[]).unwrap();
+        assert_eq!(itn.block0_hash(), HASH);
+
+        let not_hex = format!("{}g", &HASH[1..]);
+        for hash in &["", &HASH[1..], &format!("{}0", HASH), &not_hex] {
+            assert!(
+                matches!(
+                    blockchain("itn", hash, &[]),
+                    Err(Error::InvalidBlock0Hash(_))
+                ),
+                "{}",
+                hash
+            );
+        }
+    }
+
+    #[test]
+    fn valid_peers() {
+        for peer in &[
+            "/ip4/1.2.3.4/tcp/3000",
+            "/ip6/::1/tcp/3000",
+            "/ip6/2001:db8::1/tcp/65535",
+            "/dns/peer.example.com/tcp/3000",
+            "/dns4/peer-1.example.com/tcp/3000",
+            "/dns6/localhost/tcp/3000",
+            "/ip4/1.2.3.4/tcp/3000/p2p/QmPeer1",
+        ] {
+            assert!(is_peer_address(peer), "{}", peer);
+        }
+        assert!(blockchain("itn", HASH, &["/ip4/1.2.3.4/tcp/3000"]).is_ok());
+    }
+
+    #[test]
+    fn invalid_peers() {
+        for peer in &[
+            "",
+            "1.2.3.4:3000",
+            "/ip4/1.2.3.4/tcp/0",
+            "/ip4/1.2.3.4/tcp/65536",
+            "/ip4/1.2.3.4",
+            "/ip4/1.2.3.4/udp/3000",
+            "/ip4/1.2.3.4/tcp/3000/",
+            "/ip4/1.2.3.4/tcp/3000/p2p",
+            "/ip4/1.2.3.4/tcp/3000/p2p/QmPeer1/extra",
+            "/ip4/1.2.3.4/tcp/3000/http",
+            "/ip4/1.2.3/tcp/3000",
+            "/ip4/::1/tcp/3000",
+            "/ip6/1.2.3.4/tcp/3000",
+            "/dns/-peer.example.com/tcp/3000",
+            "/dns/peer..example.com/tcp/3000",
+        ] {
+            assert!(!is_peer_address(peer), "{}", peer);
+        }
+        assert!(matches!(
+            blockchain("itn", HASH, &["/ip4/1.2.3.4/tcp/0"]),
+            Err(Error::InvalidPeer(_))
+        ));
+    }
+}
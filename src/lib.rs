@@ -2,4 +2,5 @@ pub mod commands;
 pub mod common;
 pub mod config;
 pub mod jormungandr_config;
+pub mod settings;
 pub mod utils;
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+/// user settings read from `$JORUP_HOME/settings.json`
+///
+/// Every field is optional so an absent or partial file falls back to the
+/// built-in behaviour.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// minisign public keys trusted to sign release assets, replacing the
+    /// keys compiled into jorup when set
+    #[serde(default)]
+    trusted_keys: Option<Vec<String>>,
+
+    /// where to fetch releases from: `github`, a mirror URL or a local
+    /// directory
+    #[serde(default)]
+    release_source: Option<String>,
+}
+
+impl Settings {
+    pub fn trusted_keys(&self) -> Option<&[String]> {
+        self.trusted_keys.as_deref()
+    }
+
+    pub fn release_source(&self) -> Option<&str> {
+        self.release_source.as_deref()
+    }
+}
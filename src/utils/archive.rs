@@ -0,0 +1,315 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+use thiserror::Error;
+
+/// the archive formats releases are published in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+    TarXz,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot open the archive {1}")]
+    CannotOpen(#[source] io::Error, PathBuf),
+    #[error("Unknown archive format for {0}, expecting zip, tar.gz or tar.xz")]
+    UnknownFormat(PathBuf),
+    #[error("Cannot read the zip archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Cannot extract the archive")]
+    Extract(#[from] io::Error),
+    #[error("Refusing to extract {0} outside of the destination")]
+    UnsafePath(String),
+    #[error("Refusing to extract {0}: only files and directories are supported")]
+    UnsupportedEntry(String),
+}
+
+impl Format {
+    /// recognise the archive format from its first bytes
+    pub fn detect(path: &Path) -> Result<Self, Error> {
+        let mut magic = [0; 6];
+        let mut file = File::open(path).map_err(|e| Error::CannotOpen(e, path.to_path_buf()))?;
+        let read = file
+            .read(&mut magic)
+            .map_err(|e| Error::CannotOpen(e, path.to_path_buf()))?;
+
+        match &magic[..read] {
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => Ok(Format::Zip),
+            [0x1f, 0x8b, ..] => Ok(Format::TarGz),
+            [0xfd, b'7', b'z', b'X', b'Z', 0] => Ok(Format::TarXz),
+            _ => Err(Error::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+/// extract the archive into the given directory
+///
+/// Entries with an absolute path or going up with `..` are refused, as are
+/// links and other special entries: a release only holds binaries.
+pub fn unpack(archive: &Path, into: &Path) -> Result<(), Error> {
+    let format = Format::detect(archive)?;
+    let file = File::open(archive).map_err(|e| Error::CannotOpen(e, archive.to_path_buf()))?;
+
+    match format {
+        Format::Zip => unpack_zip(file, into),
+        Format::TarGz => unpack_tar(flate2::read::GzDecoder::new(file), into),
+        Format::TarXz => unpack_tar(xz2::read::XzDecoder::new(file), into),
+    }
+}
+
+fn unpack_tar<R: Read>(reader: R, into: &Path) -> Result<(), Error> {
+    use tar::EntryType;
+
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let path = match entry_path(&name)? {
+            Some(path) => into.join(path),
+            None => continue,
+        };
+
+        match entry.header().entry_type() {
+            EntryType::Directory => fs::create_dir_all(&path)?,
+            EntryType::Regular | EntryType::Continuous => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                entry.unpack(&path)?;
+            }
+            // metadata entries of the pax and GNU formats
+            EntryType::XGlobalHeader | EntryType::XHeader => {}
+            _ => return Err(Error::UnsupportedEntry(name.display().to_string())),
+        }
+    }
+
+    Ok(())
+}
+
+fn unpack_zip(file: File, into: &Path) -> Result<(), Error> {
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = PathBuf::from(file.name());
+        let path = match entry_path(&name)? {
+            Some(path) => into.join(path),
+            None => continue,
+        };
+
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut output = File::create(&path)?;
+        io::copy(&mut file, &mut output)?;
+        set_mode(&path, file.unix_mode())?;
+    }
+
+    Ok(())
+}
+
+/// the relative path an entry is extracted to, `None` for the archive root
+fn entry_path(name: &Path) -> Result<Option<PathBuf>, Error> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(Error::UnsafePath(name.display().to_string()))
+            }
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(path))
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    match mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn set_mode(_: &Path, _: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+/// find a file by name in the directory or its subdirectories, for archives
+/// holding the binaries under a top-level directory
+pub fn find_file(dir: &Path, name: &str) -> io::Result<Option<PathBuf>> {
+    let candidate = dir.join(name);
+    if candidate.is_file() {
+        return Ok(Some(candidate));
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Some(found) = find_file(&entry.path(), name)? {
+                return Ok(Some(found));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use tar::{EntryType, Header};
+
+    /// a tar entry with a raw name, bypassing the checks of `tar::Builder`
+    fn tar_entry(builder: &mut tar::Builder<Vec<u8>>, name: &str, kind: EntryType, data: &[u8]) {
+        let mut header = Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(kind);
+        header.set_mode(0o755);
+        header.set_size(data.len() as u64);
+        if kind == EntryType::Symlink || kind == EntryType::Link {
+            header.set_link_name("/etc/passwd").unwrap();
+        }
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    fn tar(entries: &[(&str, EntryType)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, kind) in entries {
+            let data: &[u8] = if *kind == EntryType::Regular {
+                b"binary"
+            } else {
+                b""
+            };
+            tar_entry(&mut builder, name, *kind, data);
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn entry_paths() {
+        assert_eq!(entry_path(Path::new(".")).unwrap(), None);
+        assert_eq!(
+            entry_path(Path::new("./release/jcli")).unwrap(),
+            Some(PathBuf::from("release").join("jcli"))
+        );
+        for unsafe_path in &["../jcli", "release/../../jcli", "/usr/bin/jcli"] {
+            assert!(
+                matches!(
+                    entry_path(Path::new(unsafe_path)),
+                    Err(Error::UnsafePath(_))
+                ),
+                "{} should be refused",
+                unsafe_path
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn prefix_paths() {
+        for unsafe_path in &[r"C:\jcli", r"C:jcli", r"\\server\share\jcli"] {
+            assert!(matches!(
+                entry_path(Path::new(unsafe_path)),
+                Err(Error::UnsafePath(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn unsafe_tar_paths() {
+        for name in &["../jcli", "release/../../jcli", "/tmp/jcli"] {
+            let dir = tempfile::tempdir().unwrap();
+            let into = dir.path().join("release");
+            let archive = tar(&[(name, EntryType::Regular)]);
+            let result = unpack_tar(archive.as_slice(), &into);
+            assert!(matches!(result, Err(Error::UnsafePath(_))), "{}", name);
+            assert!(!dir.path().join("jcli").exists());
+        }
+    }
+
+    #[test]
+    fn links_refused() {
+        for kind in &[EntryType::Symlink, EntryType::Link] {
+            let dir = tempfile::tempdir().unwrap();
+            let archive = tar(&[("jcli", *kind)]);
+            let result = unpack_tar(archive.as_slice(), dir.path());
+            assert!(
+                matches!(result, Err(Error::UnsupportedEntry(_))),
+                "{:?}",
+                kind
+            );
+            assert!(fs::symlink_metadata(dir.path().join("jcli")).is_err());
+        }
+    }
+
+    #[test]
+    fn nested_binaries_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("release.tar.gz");
+        let archive = tar(&[
+            ("jormungandr-0.9.0", EntryType::Directory),
+            ("jormungandr-0.9.0/bin", EntryType::Directory),
+            ("jormungandr-0.9.0/bin/jormungandr", EntryType::Regular),
+            ("jormungandr-0.9.0/bin/jcli", EntryType::Regular),
+        ]);
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&archive_path).unwrap(), Default::default());
+        encoder.write_all(&archive).unwrap();
+        encoder.finish().unwrap();
+
+        let into = dir.path().join("release");
+        assert_eq!(Format::detect(&archive_path).unwrap(), Format::TarGz);
+        unpack(&archive_path, &into).unwrap();
+
+        let bin = into.join("jormungandr-0.9.0").join("bin");
+        for name in &["jormungandr", "jcli"] {
+            assert_eq!(find_file(&into, name).unwrap(), Some(bin.join(name)));
+        }
+        assert_eq!(find_file(&into, "jorup").unwrap(), None);
+    }
+
+    #[test]
+    fn nested_zip_binaries_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("release.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        for name in &["jormungandr", "jcli"] {
+            zip.start_file(format!("release/{}", name), Default::default())
+                .unwrap();
+            zip.write_all(b"binary").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let into = dir.path().join("release");
+        assert_eq!(Format::detect(&archive_path).unwrap(), Format::Zip);
+        unpack(&archive_path, &into).unwrap();
+        for name in &["jormungandr", "jcli"] {
+            assert_eq!(
+                find_file(&into, name).unwrap(),
+                Some(into.join("release").join(name))
+            );
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{common::JorupConfig, utils::version::VersionReq};
+use crate::{
+    common::JorupConfig,
+    utils::{
+        lock::{self, Lock},
+        version::VersionReq,
+    },
+};
 use std::{
     io,
     path::{Path, PathBuf},
@@ -67,7 +73,7 @@ impl Blockchain {
     }
 
     pub fn get_runner_file(&self) -> PathBuf {
-        self.dir().join("running_config.json")
+        self.dir().join(crate::utils::runner::RUNNER_FILE)
     }
 
     pub fn get_genesis_block_hash(&self) -> PathBuf {
@@ -86,6 +92,15 @@ impl Blockchain {
         self.dir().join("wallet.secret.key")
     }
 
+    /// take the lock serialising the starting and stopping of the node of
+    /// this blockchain
+    pub fn lock(&self) -> Result<Lock, lock::Error> {
+        Lock::acquire(
+            &self.dir().join("node.lock"),
+            &format!("the {} node", self.entry.name()),
+        )
+    }
+
     pub fn dir(&self) -> &PathBuf {
         &self.path
     }
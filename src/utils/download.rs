@@ -1,4 +1,11 @@
+use chrono::{offset::Utc, DateTime, TimeZone as _};
 use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{
+    blocking::Response,
+    header::{self, HeaderMap, HeaderValue},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
 use std::io;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -9,6 +16,13 @@ const INDICATIF_TEMPLATE: &str =
     "[{elapsed_precise}] [{bar:40.cyan/blue}] {msg} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
 const INDICATIF_LENGTH: u64 = 100;
 
+/// environment variables holding a GitHub token, in order of preference
+const GITHUB_TOKEN_VARS: [&str; 2] = ["JORUP_GITHUB_TOKEN", "GITHUB_TOKEN"];
+
+/// how long we are willing to wait for the GitHub API rate limit to reset
+/// before giving up, in seconds
+const MAX_RATE_LIMIT_WAIT: i64 = 60;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Cannot create destination file for download: {0}")]
@@ -24,10 +38,51 @@ pub enum Error {
     NewClient(#[source] reqwest::Error),
     #[error("Cannot write to the provided destination")]
     DownloadToWriter(#[source] reqwest::Error),
+    #[error("Cannot copy local file {1}")]
+    CannotCopyLocalFile(#[source] io::Error, PathBuf),
+    #[error("GitHub API rate limit exceeded, it resets at {0}. Set GITHUB_TOKEN or JORUP_GITHUB_TOKEN to raise the limit")]
+    RateLimited(DateTime<Utc>),
+    #[error("Cannot download {0} while offline")]
+    Offline(String),
+    #[error("No cached copy of {0} is available offline")]
+    NotCached(String),
+    #[error("Cannot write the downloaded file {1}")]
+    CannotWriteDownload(#[source] io::Error, PathBuf),
+    #[error("Download incomplete: expected {expected} bytes, got {actual}, run the command again to resume")]
+    IncompleteDownload { expected: u64, actual: u64 },
+    #[error("Cannot move the downloaded file into place: {1}")]
+    CannotFinalise(#[source] io::Error, PathBuf),
 }
 
 pub struct Client {
     inner: reqwest::blocking::Client,
+    github_token: Option<String>,
+    cache: Option<Cache>,
+    wait_for_rate_limit: bool,
+}
+
+/// cache of metadata responses (release lists, checksums...) kept with their
+/// validators so they can be revalidated with conditional requests
+struct Cache {
+    dir: PathBuf,
+    offline: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+/// failure while talking to the remote server
+enum Failure {
+    Http(reqwest::Error),
+    RateLimited(DateTime<Utc>),
+    Offline(String),
+    Io(io::Error, PathBuf),
+    Incomplete { expected: u64, actual: u64 },
 }
 
 impl Client {
@@ -37,7 +92,101 @@ impl Client {
             .user_agent(APP_USER_AGENT)
             .build()
             .map_err(Error::NewClient)?;
-        Ok(Self { inner: client })
+        let github_token = GITHUB_TOKEN_VARS
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|token| !token.is_empty());
+        Ok(Self {
+            inner: client,
+            github_token,
+            cache: None,
+            wait_for_rate_limit: true,
+        })
+    }
+
+    /// give up at once when the GitHub API rate limit is exceeded rather
+    /// than waiting for it to reset, for requests that must not hold back
+    /// the command such as the update check
+    pub fn fail_on_rate_limit(mut self) -> Self {
+        self.wait_for_rate_limit = false;
+        self
+    }
+
+    /// cache metadata fetched with [`Client::fetch_metadata`] in `dir`
+    ///
+    /// When `offline` is set, metadata are only served from the cache and any
+    /// other download fails.
+    pub fn with_cache<P: Into<PathBuf>>(mut self, dir: P, offline: bool) -> Self {
+        self.cache = Some(Cache {
+            dir: dir.into(),
+            offline,
+        });
+        self
+    }
+
+    /// fetch a small metadata document, going through the cache if any
+    pub fn fetch_metadata(&mut self, what: &str, url: &str) -> Result<Vec<u8>, Error> {
+        let cache = match &self.cache {
+            Some(cache) if local_path(url).is_none() => cache,
+            _ => {
+                let mut data = Vec::new();
+                self.download_to_writer(what, url, &mut data)?;
+                return Ok(data);
+            }
+        };
+
+        let cached = cache.load(url);
+        if cache.offline {
+            return cached
+                .map(|entry| entry.body.into_bytes())
+                .ok_or_else(|| Error::NotCached(url.to_owned()));
+        }
+
+        let mut headers = HeaderMap::new();
+        if let Some(entry) = &cached {
+            let validators = [
+                (header::IF_NONE_MATCH, &entry.etag),
+                (header::IF_MODIFIED_SINCE, &entry.last_modified),
+            ];
+            for (name, value) in validators.iter() {
+                if let Some(value) = value.as_ref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                    headers.insert(name.clone(), value);
+                }
+            }
+        }
+
+        let response = self
+            .send(url, headers)
+            .map_err(|e| e.into_error(Error::DownloadToWriter))?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entry) = cached {
+                return Ok(entry.body.into_bytes());
+            }
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let etag = header(header::ETAG);
+        let last_modified = header(header::LAST_MODIFIED);
+        let body = response.text().map_err(Error::DownloadToWriter)?;
+
+        let entry = CacheEntry {
+            url: url.to_owned(),
+            etag,
+            last_modified,
+            body,
+        };
+        if let Err(err) = cache.store(&entry) {
+            eprintln!("WARN: cannot cache {}: {}", what, err);
+        }
+
+        Ok(entry.body.into_bytes())
     }
 
     pub fn download_to_writer<W: io::Write>(
@@ -46,28 +195,108 @@ impl Client {
         url: &str,
         to: &mut W,
     ) -> Result<(), Error> {
+        if let Some(path) = local_path(url) {
+            return copy_local(&path, to);
+        }
+
         self.download_internal(what, url, to)
-            .map_err(Error::DownloadToWriter)
+            .map_err(|e| e.into_error(Error::DownloadToWriter))
     }
 
+    /// download `url` into the file `to`
+    ///
+    /// The content is first written to `<to>.part` which is only renamed to
+    /// `to` once complete, so an interrupted download never looks like a
+    /// finished one. A leftover `.part` file is resumed with a `Range`
+    /// request when the server supports it, guarded by an `If-Range` with the
+    /// ETag or modification date saved in `<to>.part.validator` so a file
+    /// changed on the server is downloaded again from the start.
     pub fn download_file<P: AsRef<Path>>(
         &mut self,
         what: &str,
         url: &str,
         to: P,
     ) -> Result<(), Error> {
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(to.as_ref())
-            .map_err(|e| Error::CannotCreateDestinationFile(e, to.as_ref().to_path_buf()))?;
+        let to = to.as_ref();
+        let part = part_path(to);
 
-        self.download_internal(what, url, &mut file)
-            .map_err(|e| Error::CannotDownloadAsset {
-                source: e,
-                asset: what.to_owned(),
-                destination: to.as_ref().to_path_buf(),
-            })
+        if let Some(path) = local_path(url) {
+            let mut file = std::fs::File::create(&part)
+                .map_err(|e| Error::CannotCreateDestinationFile(e, part.clone()))?;
+            copy_local(&path, &mut file)?;
+        } else {
+            self.download_resumable(what, url, &part).map_err(|e| {
+                e.into_error(|source| Error::CannotDownloadAsset {
+                    source,
+                    asset: what.to_owned(),
+                    destination: to.to_path_buf(),
+                })
+            })?;
+        }
+
+        std::fs::rename(&part, to).map_err(|e| Error::CannotFinalise(e, to.to_path_buf()))?;
+        let _ = std::fs::remove_file(validator_path(&part));
+        Ok(())
+    }
+
+    fn download_resumable(&mut self, what: &str, url: &str, part: &Path) -> Result<(), Failure> {
+        self.check_online(url)?;
+
+        let mut offset = std::fs::metadata(part).map(|meta| meta.len()).unwrap_or(0);
+
+        // without the validator of the partial file we cannot tell whether it
+        // is still the same file on the server, start over
+        let validator_file = validator_path(part);
+        let validator = Some(&validator_file)
+            .filter(|_| offset > 0)
+            .and_then(|path| std::fs::read_to_string(path).ok());
+        let range = range_headers(offset, validator.as_deref());
+        if range.is_none() {
+            offset = 0;
+        }
+
+        // ranges apply to the encoded content, make sure it is the file itself
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("identity"),
+        );
+        let mut range_request = headers.clone();
+        range_request.extend(range.unwrap_or_default());
+
+        let response = match self.send(url, range_request) {
+            Err(Failure::Http(err)) if range_refused(offset, err.status()) => {
+                offset = 0;
+                self.send(url, headers)?
+            }
+            response => response?,
+        };
+
+        let resume = resumes(offset, response.status());
+        if !resume {
+            offset = 0;
+        }
+        match response_validator(response.headers()) {
+            Some(validator) => std::fs::write(&validator_file, validator)
+                .map_err(|e| Failure::Io(e, validator_file.clone()))?,
+            None => {
+                let _ = std::fs::remove_file(&validator_file);
+            }
+        }
+        let expected = response.content_length().map(|length| offset + length);
+
+        let mut file = open_part(part, resume).map_err(|e| Failure::Io(e, part.to_path_buf()))?;
+
+        copy_with_progress(what, response, offset, &mut file)?;
+
+        let actual = file
+            .metadata()
+            .map_err(|e| Failure::Io(e, part.to_path_buf()))?
+            .len();
+        match expected {
+            Some(expected) if expected != actual => Err(Failure::Incomplete { expected, actual }),
+            _ => Ok(()),
+        }
     }
 
     fn download_internal<W: io::Write>(
@@ -75,36 +304,254 @@ impl Client {
         what: &str,
         url: &str,
         to: &mut W,
-    ) -> std::result::Result<(), reqwest::Error> {
-        let style = ProgressStyle::default_bar().template(INDICATIF_TEMPLATE);
-        let progress = ProgressBar::new(INDICATIF_LENGTH).with_style(style);
-        progress.set_message(what);
-
-        let mut response = self
-            .inner
-            .execute(self.inner.get(url).build()?)?
-            .error_for_status()?;
-        let res = if let Some(total) = response.content_length() {
-            progress.set_length(total);
-            let mut writer = WriterWithProgress {
-                inner: to,
-                progress: &progress,
-                written: 0,
-            };
-            response.copy_to(&mut writer)
+    ) -> std::result::Result<(), Failure> {
+        self.check_online(url)?;
+
+        let response = self.send(url, HeaderMap::new())?;
+        copy_with_progress(what, response, 0, to)
+    }
+
+    fn check_online(&self, url: &str) -> Result<(), Failure> {
+        if self.cache.as_ref().map(|cache| cache.offline) == Some(true) {
+            Err(Failure::Offline(url.to_owned()))
         } else {
-            response.copy_to(to)
+            Ok(())
+        }
+    }
+
+    /// send a GET request, authenticated if it targets the GitHub API
+    ///
+    /// If the GitHub API rate limit is exceeded and resets soon enough, wait
+    /// for it and try once more.
+    fn send(&self, url: &str, headers: HeaderMap) -> Result<Response, Failure> {
+        let mut waited = false;
+        loop {
+            let mut request = self.inner.get(url).headers(headers.clone());
+            if let Some(token) = self.github_token.as_ref().filter(|_| is_github_api(url)) {
+                request = request.bearer_auth(token);
+            }
+            let response = self.inner.execute(request.build()?)?;
+
+            if let Some(reset) = rate_limit_reset(&response) {
+                let wait = reset - Utc::now();
+                if waited
+                    || !self.wait_for_rate_limit
+                    || wait > chrono::Duration::seconds(MAX_RATE_LIMIT_WAIT)
+                {
+                    return Err(Failure::RateLimited(reset));
+                }
+                eprintln!(
+                    "WARN: GitHub API rate limit reached, waiting {} seconds",
+                    wait.num_seconds().max(0)
+                );
+                std::thread::sleep(wait.to_std().unwrap_or_default());
+                waited = true;
+                continue;
+            }
+
+            return response.error_for_status().map_err(Failure::Http);
         }
-        .map(|_| ());
+    }
+}
+
+impl Failure {
+    fn into_error<F>(self, http: F) -> Error
+    where
+        F: FnOnce(reqwest::Error) -> Error,
+    {
+        match self {
+            Failure::Http(err) => http(err),
+            Failure::RateLimited(reset) => Error::RateLimited(reset),
+            Failure::Offline(url) => Error::Offline(url),
+            Failure::Io(err, path) => Error::CannotWriteDownload(err, path),
+            Failure::Incomplete { expected, actual } => {
+                Error::IncompleteDownload { expected, actual }
+            }
+        }
+    }
+}
+
+impl Cache {
+    fn path(&self, url: &str) -> PathBuf {
+        use sha2::{Digest, Sha256};
+
+        let key = hex::encode(Sha256::digest(url.as_bytes()));
+        self.dir.join(format!("{}.json", key))
+    }
 
-        if res.is_err() {
-            progress.finish_at_current_pos();
+    fn load(&self, url: &str) -> Option<CacheEntry> {
+        let content = std::fs::read(self.path(url)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&content).ok()?;
+        // guard against (improbable) key collisions
+        if entry.url == url {
+            Some(entry)
         } else {
-            progress.finish_and_clear();
+            None
         }
+    }
+
+    fn store(&self, entry: &CacheEntry) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(&entry.url);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(entry)?)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(err: reqwest::Error) -> Self {
+        Failure::Http(err)
+    }
+}
+
+fn is_github_api(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .map(|url| url.host_str() == Some("api.github.com"))
+        .unwrap_or(false)
+}
+
+/// when the rate limit resets, if the response reports it was exceeded
+fn rate_limit_reset(response: &Response) -> Option<DateTime<Utc>> {
+    if response.status() != StatusCode::FORBIDDEN
+        && response.status() != StatusCode::TOO_MANY_REQUESTS
+    {
+        return None;
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    if header("x-ratelimit-remaining")? != "0" {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")?.parse().ok()?;
+    Some(Utc.timestamp(reset, 0))
+}
+
+fn copy_with_progress<W: io::Write>(
+    what: &str,
+    mut response: Response,
+    offset: u64,
+    to: &mut W,
+) -> Result<(), Failure> {
+    let style = ProgressStyle::default_bar().template(INDICATIF_TEMPLATE);
+    let progress = ProgressBar::new(INDICATIF_LENGTH).with_style(style);
+    progress.set_message(what);
 
-        res
+    let res = if let Some(length) = response.content_length() {
+        progress.set_length(offset + length);
+        progress.set_position(offset);
+        let mut writer = WriterWithProgress {
+            inner: to,
+            progress: &progress,
+            written: offset,
+        };
+        response.copy_to(&mut writer)
+    } else {
+        response.copy_to(to)
     }
+    .map(|_| ());
+
+    if res.is_err() {
+        progress.finish_at_current_pos();
+    } else {
+        progress.finish_and_clear();
+    }
+
+    res.map_err(Failure::Http)
+}
+
+/// where a download is written until it is complete
+fn part_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    path.with_file_name(file_name)
+}
+
+/// where the validator of the partial download `part` is kept, to resume it
+/// only if the file did not change on the server
+fn validator_path(part: &Path) -> PathBuf {
+    let mut file_name = part.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".validator");
+    part.with_file_name(file_name)
+}
+
+/// the headers asking for the rest of a partial download of `offset` bytes,
+/// only if the file still has the given validator; `None` if the download
+/// cannot be resumed and must start over
+fn range_headers(offset: u64, validator: Option<&str>) -> Option<HeaderMap> {
+    if offset == 0 {
+        return None;
+    }
+    let validator = HeaderValue::from_str(validator?.trim()).ok()?;
+
+    let mut headers = HeaderMap::new();
+    let range = format!("bytes={}-", offset);
+    headers.insert(header::RANGE, HeaderValue::from_str(&range).unwrap());
+    headers.insert(header::IF_RANGE, validator);
+    Some(headers)
+}
+
+/// whether the response continues the partial download of `offset` bytes
+///
+/// The server answers with the whole file instead when it changed since the
+/// partial download or if it does not support ranges, the partial file must
+/// then be truncated.
+fn resumes(offset: u64, status: StatusCode) -> bool {
+    offset > 0 && status == StatusCode::PARTIAL_CONTENT
+}
+
+/// whether the server refused the range asked for a partial download of
+/// `offset` bytes, for example because the partial file is longer than the
+/// file on the server, in which case the download starts over
+fn range_refused(offset: u64, status: Option<StatusCode>) -> bool {
+    offset > 0 && status == Some(StatusCode::RANGE_NOT_SATISFIABLE)
+}
+
+/// open the partial download to append the rest of the file or, when not
+/// resuming, to write it again from the start
+fn open_part(part: &Path, resume: bool) -> io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(part)
+}
+
+/// the validator usable in an `If-Range` header: a strong ETag or else the
+/// modification date
+fn response_validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    header(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(header::LAST_MODIFIED))
+}
+
+/// the path of `file://` URLs, used by local release sources
+fn local_path(url: &str) -> Option<PathBuf> {
+    if !url.starts_with("file://") {
+        return None;
+    }
+    reqwest::Url::parse(url).ok()?.to_file_path().ok()
+}
+
+fn copy_local<W: io::Write>(path: &Path, to: &mut W) -> Result<(), Error> {
+    let mut file =
+        std::fs::File::open(path).map_err(|e| Error::CannotCopyLocalFile(e, path.to_path_buf()))?;
+    io::copy(&mut file, to)
+        .map(|_| ())
+        .map_err(|e| Error::CannotCopyLocalFile(e, path.to_path_buf()))
 }
 
 struct WriterWithProgress<'a, W> {
@@ -128,3 +575,81 @@ where
         self.inner.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const ETAG: &str = "\"v2\"";
+
+    #[test]
+    fn download_paths() {
+        let to = Path::new("downloads").join("jcli.tar.gz");
+        let part = part_path(&to);
+        assert_eq!(part, Path::new("downloads").join("jcli.tar.gz.part"));
+        assert_eq!(
+            validator_path(&part),
+            Path::new("downloads").join("jcli.tar.gz.part.validator")
+        );
+    }
+
+    #[test]
+    fn range_only_with_validator() {
+        assert!(range_headers(0, Some(ETAG)).is_none());
+        assert!(range_headers(10, None).is_none());
+        assert!(range_headers(10, Some("bad\nvalue")).is_none());
+
+        let headers = range_headers(10, Some(&format!("{}\n", ETAG))).unwrap();
+        assert_eq!(headers[header::RANGE], "bytes=10-");
+        assert_eq!(headers[header::IF_RANGE], ETAG);
+    }
+
+    #[test]
+    fn resume_only_partial_content() {
+        assert!(resumes(10, StatusCode::PARTIAL_CONTENT));
+        assert!(!resumes(10, StatusCode::OK));
+        assert!(!resumes(0, StatusCode::PARTIAL_CONTENT));
+    }
+
+    #[test]
+    fn restart_refused_range() {
+        assert!(range_refused(10, Some(StatusCode::RANGE_NOT_SATISFIABLE)));
+        assert!(!range_refused(0, Some(StatusCode::RANGE_NOT_SATISFIABLE)));
+        assert!(!range_refused(10, Some(StatusCode::NOT_FOUND)));
+        assert!(!range_refused(10, None));
+    }
+
+    #[test]
+    fn append_or_truncate_part() {
+        use std::io::Write as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let part = part_path(&dir.path().join("jcli.tar.gz"));
+
+        open_part(&part, false)
+            .unwrap()
+            .write_all(b"jormun")
+            .unwrap();
+        open_part(&part, true).unwrap().write_all(b"gandr").unwrap();
+        assert_eq!(std::fs::read(&part).unwrap(), b"jormungandr");
+
+        open_part(&part, false).unwrap().write_all(b"jcli").unwrap();
+        assert_eq!(std::fs::read(&part).unwrap(), b"jcli");
+    }
+
+    #[test]
+    fn strong_validators() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(response_validator(&headers), None);
+
+        let date = "Mon, 01 Jun 2020 00:00:00 GMT";
+        headers.insert(header::LAST_MODIFIED, HeaderValue::from_static(date));
+        assert_eq!(response_validator(&headers).as_deref(), Some(date));
+
+        // weak ETags cannot be used with `If-Range`
+        headers.insert(header::ETAG, HeaderValue::from_static("W/\"v2\""));
+        assert_eq!(response_validator(&headers).as_deref(), Some(date));
+
+        headers.insert(header::ETAG, HeaderValue::from_static(ETAG));
+        assert_eq!(response_validator(&headers).as_deref(), Some(ETAG));
+    }
+}
@@ -1,6 +1,11 @@
 use super::download::{self, Client};
-use crate::utils::version::{Version, VersionError, VersionReq};
+use crate::utils::{
+    release_source::{self, ReleaseSource},
+    target::{AssetKind, Target},
+    version::{Channel, Version, VersionError, VersionReq},
+};
 use chrono::{offset::Utc, DateTime};
+use reqwest::Url;
 use serde::Deserialize;
 use thiserror::Error;
 
@@ -9,21 +14,24 @@ pub const JORUP: &str = "jorup";
 
 pub struct Release {
     version: Version,
-    assets: Vec<AssetDef>,
+    assets: Vec<Asset>,
+    published_at: DateTime<Utc>,
 }
 
+/// a release as described by the GitHub API, also used by release mirrors
 #[derive(Deserialize)]
-struct ReleasesDef(Vec<ReleaseDef>);
-
-#[derive(Deserialize)]
-struct ReleaseDef {
+pub struct ReleaseDef {
     tag_name: String,
-    assets: Vec<AssetDef>,
+    assets: Vec<Asset>,
     published_at: DateTime<Utc>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
 }
 
 #[derive(Deserialize)]
-struct AssetDef {
+pub struct Asset {
     #[serde(rename = "browser_download_url")]
     url: String,
     name: String,
@@ -31,128 +39,388 @@ struct AssetDef {
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Failed to fetch releases")]
-    CannotGetReleaseData(#[from] download::Error),
-    #[error("Cannot parse the release data")]
-    MalformedReleaseData(#[from] serde_json::Error),
+    #[error(transparent)]
+    Source(#[from] release_source::Error),
     #[error("No release matching {0}")]
     ReleaseNotFound(VersionReq),
-}
-
-fn download_release_by_url(client: &mut Client, url: &str) -> Result<ReleaseDef, Error> {
-    let mut release_data_raw: Vec<u8> = Vec::new();
-    client.download_to_writer("GitHub release", &url, &mut release_data_raw)?;
-    serde_json::from_slice(&release_data_raw).map_err(Into::into)
+    #[error("Unrecognised release tag {0}")]
+    InvalidTag(String, #[source] VersionError),
 }
 
 fn get_exact_release(
+    source: &dyn ReleaseSource,
     client: &mut Client,
     repo: &str,
     version: VersionReq,
 ) -> Result<Release, Error> {
     let version = version.into_version().unwrap();
-    let url = format!(
-        "https://api.github.com/repos/input-output-hk/{}/releases/tags/{}",
-        repo,
-        version.to_git_tag(),
-    );
-    let release_def = download_release_by_url(client, &url)?;
+    let release_def = source.by_tag(client, repo, &version.to_git_tag())?;
     Ok(Release {
         version,
         assets: release_def.assets,
+        published_at: release_def.published_at,
     })
 }
 
-fn get_latest_release(client: &mut Client, repo: &str) -> Result<Release, Error> {
-    let release_def = download_release_by_url(
-        client,
-        &format!(
-            "https://api.github.com/repos/input-output-hk/{}/releases/latest",
-            repo
-        ),
-    )?;
-    let version = Version::from_git_tag(&release_def.tag_name).unwrap();
-    Ok(Release {
-        version,
-        assets: release_def.assets,
-    })
+fn get_latest_release(
+    source: &dyn ReleaseSource,
+    client: &mut Client,
+    repo: &str,
+) -> Result<Release, Error> {
+    let release_def = source.latest(client, repo)?;
+    let tag_name = release_def.tag_name.clone();
+    Release::from_def(release_def).map_err(|e| Error::InvalidTag(tag_name, e))
 }
 
-fn get_nightly_release(client: &mut Client, repo: &str) -> Result<Release, Error> {
-    let release_def = download_release_by_url(
-        client,
-        &format!(
-            "https://api.github.com/repos/input-output-hk/{}/releases/tags/nightly",
-            repo
-        ),
-    )?;
-    let version = Version::from_git_tag(&release_def.tag_name)
-        .unwrap()
-        .configure_nightly(release_def.published_at.date());
-    Ok(Release {
-        version,
-        assets: release_def.assets,
-    })
+fn get_nightly_release(
+    source: &dyn ReleaseSource,
+    client: &mut Client,
+    repo: &str,
+) -> Result<Release, Error> {
+    let release_def = source.by_tag(client, repo, "nightly")?;
+    let tag_name = release_def.tag_name.clone();
+    Release::from_def(release_def).map_err(|e| Error::InvalidTag(tag_name, e))
 }
 
 fn find_release_by_req(
+    source: &dyn ReleaseSource,
     client: &mut Client,
     repo: &str,
     version_req: &VersionReq,
 ) -> Result<Release, Error> {
-    let mut releases_data_raw: Vec<u8> = Vec::new();
-    client.download_to_writer(
-        "GitHub releases",
-        &format!(
-            "https://api.github.com/repos/input-output-hk/{}/releases",
-            repo
-        ),
-        &mut releases_data_raw,
-    )?;
-
-    let releases: ReleasesDef = serde_json::from_slice(&releases_data_raw)?;
-
-    let release = releases
-        .0
-        .into_iter()
-        .map(|release_def| {
-            Ok::<_, VersionError>(Release {
-                version: Version::from_git_tag(&release_def.tag_name)?,
-                assets: release_def.assets,
-            })
-        })
-        .filter_map(core::result::Result::ok)
-        .find(|release| version_req.matches(&release.version));
+    for page in 1.. {
+        let releases = match source.releases_page(client, repo, page) {
+            Ok(releases) => releases,
+            // offline, only the pages fetched before are known
+            Err(err) if page > 1 && is_not_cached(&err) => break,
+            Err(err) => return Err(err.into()),
+        };
+        if releases.is_empty() {
+            break;
+        }
+
+        // releases flagged as pre-releases on GitHub stay off the stable
+        // channel whatever their tag
+        let stable = version_req.channel() == Channel::Stable;
+        for release_def in releases
+            .into_iter()
+            .filter(|release| !release.draft && (!stable || !release.prerelease))
+        {
+            if let Some(release) = parse_release(release_def) {
+                if version_req.matches(release.version()) {
+                    return Ok(release);
+                }
+            }
+        }
+    }
+
+    Err(Error::ReleaseNotFound(version_req.clone()))
+}
+
+/// all the published releases of the repository, drafts excluded, in the
+/// order of the release source (newest first for GitHub)
+pub fn list_releases(
+    source: &dyn ReleaseSource,
+    client: &mut Client,
+    repo: &str,
+) -> Result<Vec<Release>, Error> {
+    let mut all = Vec::new();
+    for page in 1.. {
+        let releases = source.releases_page(client, repo, page)?;
+        if releases.is_empty() {
+            break;
+        }
 
-    match release {
-        Some(release) => Ok(release),
-        None => Err(Error::ReleaseNotFound(version_req.clone())),
+        all.extend(
+            releases
+                .into_iter()
+                .filter(|release| !release.draft)
+                .filter_map(parse_release),
+        );
+    }
+    Ok(all)
+}
+
+fn parse_release(release_def: ReleaseDef) -> Option<Release> {
+    let tag_name = release_def.tag_name.clone();
+    match Release::from_def(release_def) {
+        Ok(release) => Some(release),
+        Err(err) => {
+            eprintln!(
+                "WARN: ignoring release with unrecognised tag {}: {}",
+                tag_name, err
+            );
+            None
+        }
     }
 }
 
 pub fn find_matching_release(
+    source: &dyn ReleaseSource,
     client: &mut Client,
     repo: &str,
     version_req: VersionReq,
 ) -> Result<Release, Error> {
     match version_req {
-        VersionReq::Latest => get_latest_release(client, repo),
-        VersionReq::Nightly => get_nightly_release(client, repo),
-        VersionReq::Stable(_) => find_release_by_req(client, repo, &version_req),
-        VersionReq::ExactStable(_) => get_exact_release(client, repo, version_req),
+        VersionReq::Latest => get_latest_release(source, client, repo),
+        VersionReq::Nightly => get_nightly_release(source, client, repo),
+        VersionReq::Prerelease | VersionReq::Stable(_) | VersionReq::NightlyRange(_) => {
+            find_release_by_req(source, client, repo, &version_req)
+        }
+        // offline, the release may only have been seen in the list of
+        // releases rather than looked up by its tag
+        VersionReq::ExactStable(_) => get_exact_release(source, client, repo, version_req.clone())
+            .or_else(|err| match &err {
+                Error::Source(source_err) if is_not_cached(source_err) => {
+                    find_release_by_req(source, client, repo, &version_req).map_err(|_| err)
+                }
+                _ => Err(err),
+            }),
+        // dated nightlies are tagged `nightly.YYYYMMDD`, look through the
+        // releases in case it was published differently
+        VersionReq::ExactNightly(_) => get_exact_release(source, client, repo, version_req.clone())
+            .or_else(|_| find_release_by_req(source, client, repo, &version_req)),
     }
 }
 
+/// whether the release data are missing from the cache while offline
+fn is_not_cached(err: &release_source::Error) -> bool {
+    matches!(
+        err,
+        release_source::Error::CannotGetReleaseData(download::Error::NotCached(_))
+    )
+}
+
 impl Release {
-    pub fn get_asset_url(&self, platform: &str) -> Option<&str> {
-        let maybe_asset = self
-            .assets
+    fn from_def(release_def: ReleaseDef) -> Result<Self, VersionError> {
+        let version = Version::from_git_tag(&release_def.tag_name)?
+            .configure_nightly(release_def.published_at.date());
+        Ok(Release {
+            version,
+            assets: release_def.assets,
+            published_at: release_def.published_at,
+        })
+    }
+
+    /// find the asset of the given kind best fitting the target platform
+    pub fn get_asset(&self, target: &Target, kind: AssetKind) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .filter(|asset| !is_auxiliary_asset(&asset.name))
+            .filter_map(|asset| target.score(&asset.name, kind).map(|score| (score, asset)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, asset)| asset)
+    }
+
+    /// names of the assets published with the release, to report what is
+    /// available when nothing fits the platform
+    pub fn asset_names(&self) -> Vec<String> {
+        self.assets
             .iter()
-            .find(|asset| asset.name.contains(platform));
-        maybe_asset.map(|asset| &asset.url[..])
+            .filter(|asset| !is_auxiliary_asset(&asset.name))
+            .map(|asset| asset.name.clone())
+            .collect()
+    }
+
+    /// find the asset publishing the SHA-256 digest of the given asset,
+    /// either as a dedicated `<asset>.sha256` file or as a `SHA256SUMS` list
+    pub fn get_checksum_asset(&self, asset: &Asset) -> Option<&Asset> {
+        let dedicated = [
+            format!("{}.sha256", asset.name),
+            format!("{}.sha256sum", asset.name),
+        ];
+        self.assets
+            .iter()
+            .find(|other| dedicated.contains(&other.name))
+            .or_else(|| {
+                self.assets
+                    .iter()
+                    .find(|other| other.name.eq_ignore_ascii_case("SHA256SUMS"))
+            })
+    }
+
+    /// find the detached minisign signature (`<asset>.minisig`) of the given
+    /// asset
+    pub fn get_signature_asset(&self, asset: &Asset) -> Option<&Asset> {
+        let name = format!("{}.minisig", asset.name);
+        self.assets.iter().find(|other| other.name == name)
     }
 
     pub fn version(&self) -> &Version {
         &self.version
     }
+
+    pub fn published_at(&self) -> &DateTime<Utc> {
+        &self.published_at
+    }
+}
+
+impl ReleaseDef {
+    pub fn tag_name(&self) -> &str {
+        &self.tag_name
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.draft
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        self.prerelease
+    }
+
+    /// resolve asset URLs relative to the location of the release index
+    pub(crate) fn rebase_assets(mut self, base: &Url) -> Self {
+        for asset in self.assets.iter_mut() {
+            if let Ok(url) = base.join(&asset.url) {
+                asset.url = url.into_string();
+            }
+        }
+        self
+    }
+}
+
+impl Asset {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// checksums and signatures published next to the actual release assets
+fn is_auxiliary_asset(name: &str) -> bool {
+    name.ends_with(".sha256")
+        || name.ends_with(".sha256sum")
+        || name.ends_with(".minisig")
+        || name == "SHA256SUMS"
+}
+
+/// extract the digest of `asset_name` from the content of a checksum file
+///
+/// Lines are expected in the `sha256sum` output format (`<digest>  <name>`).
+/// A line with only a digest is accepted as the digest of the asset since
+/// dedicated checksum files do not always repeat the file name.
+pub fn parse_checksum(content: &str, asset_name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let digest = parts.next()?;
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        match parts.next() {
+            None => Some(digest.to_ascii_lowercase()),
+            Some(name) if name.trim_start_matches('*') == asset_name => {
+                Some(digest.to_ascii_lowercase())
+            }
+            Some(_) => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    const ASSET: &str = "jormungandr-v0.9.0-x86_64-unknown-linux-gnu-generic.tar.gz";
+
+    #[test]
+    fn checksum_of_named_asset() {
+        let other = "0000000000000000000000000000000000000000000000000000000000000000";
+        let content = format!("{}  other.tar.gz\n{}  {}\n", other, DIGEST, ASSET);
+        assert_eq!(parse_checksum(&content, ASSET).as_deref(), Some(DIGEST));
+    }
+
+    #[test]
+    fn checksum_in_binary_mode() {
+        let content = format!("{} *{}\n", DIGEST, ASSET);
+        assert_eq!(parse_checksum(&content, ASSET).as_deref(), Some(DIGEST));
+    }
+
+    #[test]
+    fn checksum_without_name() {
+        let content = format!("{}\n", DIGEST.to_ascii_uppercase());
+        assert_eq!(parse_checksum(&content, ASSET).as_deref(), Some(DIGEST));
+    }
+
+    #[test]
+    fn checksum_of_other_asset() {
+        let content = format!("{}  other.tar.gz\n", DIGEST);
+        assert_eq!(parse_checksum(&content, ASSET), None);
+    }
+
+    #[test]
+    fn invalid_digests() {
+        for digest in &[&DIGEST[1..], &DIGEST.replace('9', "g"), ""] {
+            let content = format!("{}  {}\n", digest, ASSET);
+            assert_eq!(parse_checksum(&content, ASSET), None, "{}", digest);
+        }
+    }
+
+    #[test]
+    fn unrecognised_latest_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join(JORUP);
+        std::fs::create_dir(&index).unwrap();
+        std::fs::write(
+            index.join("releases.json"),
+            r#"[{"tag_name":"latest-build","published_at":"2020-06-01T00:00:00Z","assets":[]}]"#,
+        )
+        .unwrap();
+
+        let source = release_source::LocalDirectory::new(dir.path()).unwrap();
+        let mut client = Client::new().unwrap();
+        assert!(matches!(
+            get_latest_release(&source, &mut client, JORUP),
+            Err(Error::InvalidTag(tag, _)) if tag == "latest-build"
+        ));
+    }
+
+    #[test]
+    fn exact_release_from_cached_list() {
+        use sha2::{Digest, Sha256};
+
+        // only the first page of the releases was fetched, as by
+        // `node install ^0.9` or `node list --remote`
+        let cache = tempfile::tempdir().unwrap();
+        let url = format!(
+            "https://api.github.com/repos/input-output-hk/{}/releases?per_page=100&page=1",
+            JORMUNGANDR
+        );
+        let entry = serde_json::json!({
+            "url": url,
+            "etag": null,
+            "last_modified": null,
+            "body": r#"[{"tag_name":"v0.9.0","published_at":"2020-06-01T00:00:00Z","assets":[]}]"#,
+        });
+        let key = hex::encode(Sha256::digest(url.as_bytes()));
+        std::fs::write(
+            cache.path().join(format!("{}.json", key)),
+            entry.to_string(),
+        )
+        .unwrap();
+
+        let source = release_source::GitHub;
+        let mut client = Client::new().unwrap().with_cache(cache.path(), true);
+        let release = find_matching_release(
+            &source,
+            &mut client,
+            JORMUNGANDR,
+            VersionReq::parse("0.9.0").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(release.version().to_string(), "0.9.0");
+
+        assert!(matches!(
+            find_matching_release(
+                &source,
+                &mut client,
+                JORMUNGANDR,
+                VersionReq::parse("0.8.0").unwrap(),
+            ),
+            Err(Error::Source(err)) if is_not_cached(&err)
+        ));
+    }
 }
@@ -1,6 +1,9 @@
-use crate::utils::{
-    download, github,
-    version::{Version, VersionReq},
+use crate::{
+    common::JorupConfig,
+    utils::{
+        download, github,
+        version::{Version, VersionReq},
+    },
 };
 use thiserror::Error;
 
@@ -12,15 +15,27 @@ pub enum Error {
     Release(#[source] github::Error),
 }
 
-pub fn check_jorup_update() -> Result<Option<github::Release>, Error> {
-    check_update(github::JORUP)
+pub fn check_jorup_update(cfg: &JorupConfig) -> Result<Option<github::Release>, Error> {
+    let mut client = cfg.client().map_err(Error::Client)?;
+    check_update(cfg, &mut client, github::JORUP)
 }
 
-pub fn check_update(app: &str) -> Result<Option<github::Release>, Error> {
+/// check for a jorup update without waiting for the GitHub API rate limit to
+/// reset, for the check made before running any command
+pub fn check_jorup_update_quickly(cfg: &JorupConfig) -> Result<Option<github::Release>, Error> {
+    let mut client = cfg.client().map_err(Error::Client)?.fail_on_rate_limit();
+    check_update(cfg, &mut client, github::JORUP)
+}
+
+pub fn check_update(
+    cfg: &JorupConfig,
+    client: &mut download::Client,
+    app: &str,
+) -> Result<Option<github::Release>, Error> {
     let current_version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
-    let mut client = download::Client::new().map_err(Error::Client)?;
-    let available_release = github::find_matching_release(&mut client, app, VersionReq::Latest)
-        .map_err(Error::Release)?;
+    let available_release =
+        github::find_matching_release(cfg.release_source(), client, app, VersionReq::Latest)
+            .map_err(Error::Release)?;
     let res = if &current_version < available_release.version() {
         Some(available_release)
     } else {
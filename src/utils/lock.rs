@@ -0,0 +1,79 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// an advisory lock on a file, released when dropped
+///
+/// The lock file holds the pid of the process owning the lock so the ones
+/// waiting for it can tell who they are waiting for.
+#[derive(Debug)]
+pub struct Lock {
+    file: File,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot open the lock file {1}")]
+    CannotOpen(#[source] io::Error, PathBuf),
+    #[error("Cannot take the lock {1}")]
+    CannotLock(#[source] io::Error, PathBuf),
+}
+
+impl Lock {
+    /// take an exclusive lock on the file at `path`, waiting for the process
+    /// holding it if any. `what` names what the lock protects in the message
+    /// printed while waiting
+    pub fn acquire(path: &Path, what: &str) -> Result<Self, Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| Error::CannotOpen(e, path.to_path_buf()))?;
+
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                match holder(&mut file) {
+                    Some(pid) => {
+                        eprintln!("**** waiting for the lock on {} held by pid {}", what, pid)
+                    }
+                    None => eprintln!("**** waiting for the lock on {}", what),
+                }
+                file.lock()
+                    .map_err(|e| Error::CannotLock(e, path.to_path_buf()))?;
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(Error::CannotLock(err, path.to_path_buf()))
+            }
+        }
+
+        // knowing the owner is only informative
+        let _ = file
+            .set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(file, "{}", std::process::id()));
+
+        Ok(Lock { file })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// the pid written by the process holding the lock
+///
+/// Windows locks are mandatory, the file cannot be read while locked.
+fn holder(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
@@ -0,0 +1,145 @@
+use crate::utils::target::Target;
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// how and when a release was installed, kept as `manifest.json` in the
+/// release directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// where the release comes from: the asset URL, a local path or a git
+    /// repository
+    source: String,
+    /// the name of the release archive, if installed from one
+    asset: Option<String>,
+    sha256: Option<String>,
+    size: Option<u64>,
+    installed_at: DateTime<Utc>,
+    target: String,
+    /// the output of `jormungandr --version`
+    jormungandr_version: Option<String>,
+    /// the output of `jcli --version`
+    jcli_version: Option<String>,
+    /// when the release was last run through the proxies or `jorup run`
+    #[serde(default)]
+    last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot read the manifest {1}")]
+    CannotRead(#[source] io::Error, PathBuf),
+    #[error("Invalid manifest {1}")]
+    Malformed(#[source] serde_json::Error, PathBuf),
+    #[error("Cannot write the manifest {1}")]
+    CannotWrite(#[source] io::Error, PathBuf),
+}
+
+impl Manifest {
+    pub fn new(source: String, target: &Target) -> Self {
+        Manifest {
+            source,
+            asset: None,
+            sha256: None,
+            size: None,
+            installed_at: Utc::now(),
+            target: target.to_string(),
+            jormungandr_version: None,
+            jcli_version: None,
+            last_used: None,
+        }
+    }
+
+    pub fn with_asset(mut self, name: String, sha256: String, size: u64) -> Self {
+        self.asset = Some(name);
+        self.sha256 = Some(sha256);
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_versions(mut self, jormungandr: Option<String>, jcli: Option<String>) -> Self {
+        self.jormungandr_version = jormungandr;
+        self.jcli_version = jcli;
+        self
+    }
+
+    /// whether the release directory has a manifest
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(MANIFEST_FILE).is_file()
+    }
+
+    /// load the manifest of a release directory, `None` for releases
+    /// installed before manifests were written
+    pub fn load(dir: &Path) -> Result<Option<Self>, Error> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::CannotRead(err, path)),
+        };
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|e| Error::Malformed(e, path))
+    }
+
+    /// write the manifest, replacing the previous one in one step as it
+    /// may be updated by concurrent runs of the proxies
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        let path = dir.join(MANIFEST_FILE);
+        let tmp = dir.join(format!(".{}.{}", MANIFEST_FILE, std::process::id()));
+        let content = serde_json::to_vec_pretty(self).unwrap();
+        fs::write(&tmp, content)
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                Error::CannotWrite(e, path)
+            })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn asset(&self) -> Option<&str> {
+        self.asset.as_deref()
+    }
+
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    pub fn installed_at(&self) -> &DateTime<Utc> {
+        &self.installed_at
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn jormungandr_version(&self) -> Option<&str> {
+        self.jormungandr_version.as_deref()
+    }
+
+    pub fn jcli_version(&self) -> Option<&str> {
+        self.jcli_version.as_deref()
+    }
+
+    /// when the release was last used, or installed if it never was
+    pub fn last_used(&self) -> &DateTime<Utc> {
+        self.last_used.as_ref().unwrap_or(&self.installed_at)
+    }
+
+    pub fn mark_used(&mut self) {
+        self.last_used = Some(Utc::now());
+    }
+}
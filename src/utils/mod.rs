@@ -1,12 +1,22 @@
+pub mod archive;
 pub mod blockchain;
 pub mod download;
 pub mod github;
 pub mod jcli;
 pub mod jorup_update;
+pub mod lock;
+pub mod manifest;
+pub mod pin;
 mod print_error;
+pub mod proxy;
+pub mod registry;
 pub mod release;
+pub mod release_source;
 pub mod runner;
+pub mod signature;
+pub mod source_build;
+pub mod target;
 pub mod version;
 
-pub use jorup_update::check_jorup_update;
+pub use jorup_update::{check_jorup_update, check_jorup_update_quickly};
 pub use print_error::print_error;
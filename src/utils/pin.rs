@@ -0,0 +1,223 @@
+use crate::utils::version::{VersionReq, VersionReqError};
+use serde::Deserialize;
+use std::{
+    env, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// the pin files looked for, in this order, in each directory
+const PIN_FILES: &[&str] = &[".jorup-version", "jorup.toml"];
+
+/// the blockchain and jormungandr version a project works with
+///
+/// Read from a `.jorup-version` or `jorup.toml` file found in the current
+/// directory or one of its parents:
+///
+/// ```toml
+/// blockchain = "testnet"
+/// version = "^0.9"
+/// rest_listen = "127.0.0.1:8443"
+/// ```
+///
+/// `.jorup-version` may also hold a single `<blockchain> [<version>]` line.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pin {
+    blockchain: Option<String>,
+    #[serde(default)]
+    version: Option<VersionReq>,
+    #[serde(default)]
+    rest_listen: Option<SocketAddr>,
+
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot get the current directory")]
+    CurrentDir(#[source] io::Error),
+    #[error("Cannot read the pin file {1}")]
+    CannotRead(#[source] io::Error, PathBuf),
+    #[error("Invalid pin file {1}")]
+    Malformed(#[source] toml::de::Error, PathBuf),
+    #[error("Invalid version requirement in the pin file {1}")]
+    InvalidVersion(#[source] VersionReqError, PathBuf),
+    #[error("No blockchain given and none pinned in a .jorup-version or jorup.toml file")]
+    NoBlockchain,
+}
+
+impl Pin {
+    /// look for a pin file in the current directory and its parents
+    pub fn find() -> Result<Option<Self>, Error> {
+        let current_dir = env::current_dir().map_err(Error::CurrentDir)?;
+        Self::find_from(&current_dir)
+    }
+
+    pub fn find_from(dir: &Path) -> Result<Option<Self>, Error> {
+        for dir in dir.ancestors() {
+            for file_name in PIN_FILES {
+                let path = dir.join(file_name);
+                if path.is_file() {
+                    return Self::load(path).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn load(path: PathBuf) -> Result<Self, Error> {
+        let content = fs::read_to_string(&path).map_err(|e| Error::CannotRead(e, path.clone()))?;
+
+        // a line such as `itn >=0.8.18` may also be read as TOML, the short
+        // form is tried first and the TOML error only reported if neither fit
+        let mut line_error = None;
+        if path.ends_with(PIN_FILES[0]) {
+            match Self::parse_line(&content, &path) {
+                Some(Ok(pin)) => return Ok(pin),
+                Some(Err(err)) => line_error = Some(err),
+                None => (),
+            }
+        }
+
+        let mut pin = toml::from_str::<Pin>(&content)
+            .map_err(|err| line_error.unwrap_or(Error::Malformed(err, path.clone())))?;
+        pin.path = path;
+        Ok(pin)
+    }
+
+    /// parse the short `<blockchain> [<version>]` form, `None` if the content
+    /// does not have this shape
+    fn parse_line(content: &str, path: &Path) -> Option<Result<Self, Error>> {
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let line = lines.next()?;
+        if lines.next().is_some() {
+            return None;
+        }
+
+        let mut words = line.split_whitespace();
+        let blockchain = words.next()?.to_owned();
+        let version = words.next();
+        if words.next().is_some() || !crate::config::is_valid_name(&blockchain) {
+            return None;
+        }
+
+        let version = match version.map(VersionReq::parse).transpose() {
+            Ok(version) => version,
+            Err(err) => return Some(Err(Error::InvalidVersion(err, path.to_path_buf()))),
+        };
+        Some(Ok(Pin {
+            blockchain: Some(blockchain),
+            version,
+            rest_listen: None,
+            path: path.to_path_buf(),
+        }))
+    }
+
+    pub fn blockchain(&self) -> Option<&str> {
+        self.blockchain.as_deref()
+    }
+
+    pub fn version_req(&self) -> Option<&VersionReq> {
+        self.version.as_ref()
+    }
+
+    pub fn rest_listen(&self) -> Option<SocketAddr> {
+        self.rest_listen
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// the blockchain to use: the one given on the command line, or else the one
+/// pinned for the current directory along with the rest of the pin
+///
+/// The pin is only applied when no blockchain is given explicitly.
+pub fn select_blockchain(blockchain: Option<String>) -> Result<(String, Option<Pin>), Error> {
+    if let Some(blockchain) = blockchain {
+        return Ok((blockchain, None));
+    }
+
+    let pin = Pin::find()?.ok_or(Error::NoBlockchain)?;
+    let blockchain = pin.blockchain().ok_or(Error::NoBlockchain)?.to_owned();
+    eprintln!(
+        "**** using blockchain {} pinned in {}",
+        blockchain,
+        pin.path().display()
+    );
+    Ok((blockchain, Some(pin)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file_name: &str, content: &str) -> Result<Pin, Error> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(file_name);
+        fs::write(&path, content).unwrap();
+        Pin::load(path)
+    }
+
+    fn version(pin: &Pin) -> Option<String> {
+        pin.version_req().map(ToString::to_string)
+    }
+
+    #[test]
+    fn line_form() {
+        let pin = load(".jorup-version", "itn\n").unwrap();
+        assert_eq!(pin.blockchain(), Some("itn"));
+        assert_eq!(version(&pin), None);
+
+        let pin = load(".jorup-version", "itn 0.9.0\n").unwrap();
+        assert_eq!(version(&pin).as_deref(), Some("0.9.0"));
+    }
+
+    #[test]
+    fn line_form_with_operators() {
+        for (line, req) in &[
+            ("itn =0.9.0", "= 0.9.0"),
+            ("itn >=0.8.18", ">= 0.8.18"),
+            ("itn nightly>=20200601", "nightly>=20200601"),
+        ] {
+            let pin = load(".jorup-version", line).unwrap();
+            assert_eq!(pin.blockchain(), Some("itn"), "{}", line);
+            assert_eq!(version(&pin).as_deref(), Some(*req), "{}", line);
+        }
+    }
+
+    #[test]
+    fn toml_form() {
+        let content =
+            "blockchain = \"itn\"\nversion = \"^0.9\"\nrest_listen = \"127.0.0.1:8443\"\n";
+        for file_name in PIN_FILES {
+            let pin = load(file_name, content).unwrap();
+            assert_eq!(pin.blockchain(), Some("itn"));
+            assert!(version(&pin).is_some());
+            assert_eq!(pin.rest_listen(), Some("127.0.0.1:8443".parse().unwrap()));
+        }
+
+        let pin = load(".jorup-version", "blockchain = \"itn\"").unwrap();
+        assert_eq!(pin.blockchain(), Some("itn"));
+    }
+
+    #[test]
+    fn invalid_pins() {
+        assert!(matches!(
+            load(".jorup-version", "itn 0.9"),
+            Err(Error::InvalidVersion(..))
+        ));
+        assert!(matches!(
+            load("jorup.toml", "itn 0.9.0"),
+            Err(Error::Malformed(..))
+        ));
+        assert!(matches!(
+            load(".jorup-version", "itn 0.9.0 extra"),
+            Err(Error::Malformed(..))
+        ));
+    }
+}
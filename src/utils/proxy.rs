@@ -0,0 +1,161 @@
+use crate::{
+    common::JorupConfig,
+    utils::{
+        pin::{self, Pin},
+        release::{self, Release},
+        version::{VersionReq, VersionReqError},
+    },
+};
+use std::{
+    env::{self, consts::EXE_SUFFIX},
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+/// the tools jorup installs proxies for in `$JORUP_HOME/bin`
+const TOOLS: &[&str] = &["jormungandr", "jcli"];
+
+/// the environment variable selecting the release run by the proxies
+const VERSION_VAR: &str = "JORUP_VERSION";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot locate the jorup home directory from {0}")]
+    NoHome(PathBuf),
+    #[error(transparent)]
+    Common(#[from] crate::common::Error),
+    #[error("Invalid version requirement {1}")]
+    InvalidVersion(#[source] VersionReqError, String),
+    #[error("Cannot read the pin file")]
+    Pin(#[source] pin::Error),
+    #[error("No default release, set one with `jorup node default <version>`")]
+    NoDefault,
+    #[error("Cannot select the release to run")]
+    Release(#[source] release::Error),
+    #[error("Cannot run {1}")]
+    Exec(#[source] io::Error, PathBuf),
+}
+
+/// the tool jorup stands in for when run through one of its proxies
+pub fn tool_name(executable: &Path) -> Option<&'static str> {
+    let stem = executable.file_stem()?.to_str()?;
+    TOOLS.iter().copied().find(|tool| *tool == stem)
+}
+
+/// run `tool` from the release selected for this invocation
+///
+/// The release is picked from a `+<version>` first argument, then the
+/// `JORUP_VERSION` environment variable, then the version pinned for the
+/// current directory and finally the default release. The proxy lives in
+/// `$JORUP_HOME/bin`, which gives the jorup home.
+///
+/// On unix the proxy process is replaced by the tool, elsewhere the exit
+/// code of the tool is returned.
+pub fn run(tool: &str, executable: &Path, mut args: Vec<OsString>) -> Result<i32, Error> {
+    let home = executable
+        .parent()
+        .and_then(Path::parent)
+        .ok_or_else(|| Error::NoHome(executable.to_path_buf()))?;
+    let cfg = JorupConfig::new(Some(home.to_path_buf()), None, true, None)?;
+
+    let version = args
+        .first()
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| arg.strip_prefix('+'))
+        .map(str::to_owned);
+    let version_req = match version {
+        Some(version) => {
+            args.remove(0);
+            Some(parse_version_req(&version)?)
+        }
+        None => None,
+    };
+
+    let release = select_release(&cfg, version_req)?;
+    let path = match tool {
+        "jcli" => release.get_jcli(),
+        _ => release.get_jormungandr(),
+    };
+    release.mark_used();
+    exec(&path, args)
+}
+
+fn select_release(cfg: &JorupConfig, version_req: Option<VersionReq>) -> Result<Release, Error> {
+    let version_req = match version_req {
+        Some(version_req) => Some(version_req),
+        None => match env::var(VERSION_VAR) {
+            Ok(version) => Some(parse_version_req(&version)?),
+            Err(_) => Pin::find()
+                .map_err(Error::Pin)?
+                .and_then(|pin| pin.version_req().cloned()),
+        },
+    };
+
+    match version_req {
+        Some(version_req) => Release::load(cfg, &version_req).map_err(|err| {
+            eprintln!("HINT: run `jorup node install -v {}`", version_req);
+            Error::Release(err)
+        }),
+        None => Release::current_default(cfg).ok_or(Error::NoDefault),
+    }
+}
+
+fn parse_version_req(version: &str) -> Result<VersionReq, Error> {
+    VersionReq::parse(version).map_err(|e| Error::InvalidVersion(e, version.to_owned()))
+}
+
+#[cfg(unix)]
+fn exec(path: &Path, args: Vec<OsString>) -> Result<i32, Error> {
+    use std::os::unix::process::CommandExt as _;
+
+    let err = Command::new(path).args(args).exec();
+    Err(Error::Exec(err, path.to_path_buf()))
+}
+
+#[cfg(windows)]
+fn exec(path: &Path, args: Vec<OsString>) -> Result<i32, Error> {
+    let status = Command::new(path)
+        .args(args)
+        .status()
+        .map_err(|e| Error::Exec(e, path.to_path_buf()))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// install the `jormungandr` and `jcli` proxies in `$JORUP_HOME/bin`
+///
+/// The proxies are hard links to the installed jorup (or copies where hard
+/// links are not supported), replacing whatever was there before including
+/// the symbolic links of older jorup versions.
+pub fn install(cfg: &JorupConfig) -> io::Result<()> {
+    let jorup = cfg.bin_dir().join(format!("jorup{}", EXE_SUFFIX));
+    let jorup = if jorup.is_file() {
+        jorup
+    } else {
+        env::current_exe()?
+    };
+
+    for tool in TOOLS {
+        let proxy = cfg.bin_dir().join(format!("{}{}", tool, EXE_SUFFIX));
+        if proxy.symlink_metadata().is_ok() {
+            fs::remove_file(&proxy)?;
+        }
+        if fs::hard_link(&jorup, &proxy).is_err() {
+            fs::copy(&jorup, &proxy)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// the proxies of `$JORUP_HOME/bin` that are missing or dangling links, as
+/// left behind when the release older jorup versions linked to was removed
+pub fn broken(cfg: &JorupConfig) -> Vec<PathBuf> {
+    TOOLS
+        .iter()
+        .map(|tool| cfg.bin_dir().join(format!("{}{}", tool, EXE_SUFFIX)))
+        .filter(|proxy| !proxy.is_file())
+        .collect()
+}
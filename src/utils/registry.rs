@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// the name of the jorfile maintained in the jorup repository
+pub const UPSTREAM: &str = "upstream";
+
+/// where the upstream jorfile is synced from
+pub const UPSTREAM_URL: &str =
+    "https://raw.githubusercontent.com/input-output-hk/jorup/master/jorfile.json";
+
+/// an additional jorfile, such as one listing private testnets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registry {
+    name: String,
+    /// an `http(s)://` or `file://` URL
+    url: String,
+}
+
+/// the registries added by the user, kept in `$JORUP_HOME/registries.json`
+/// in priority order
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registries(Vec<Registry>);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot read the registries file {1}")]
+    CannotRead(#[source] io::Error, PathBuf),
+    #[error("Invalid registries file {1}")]
+    Malformed(#[source] serde_json::Error, PathBuf),
+    #[error("Cannot write the registries file {1}")]
+    CannotWrite(#[source] io::Error, PathBuf),
+    #[error("Invalid registry name {0}, expecting letters, digits, `-` or `_`")]
+    InvalidName(String),
+    #[error("A registry named {0} already exists")]
+    AlreadyExists(String),
+    #[error("No registry named {0}")]
+    NotFound(String),
+    #[error("Cannot find the jorfile {1}")]
+    InvalidPath(#[source] io::Error, PathBuf),
+    #[error("Cannot make a file URL of {0}")]
+    NotAFileUrl(PathBuf),
+}
+
+impl Registry {
+    /// a registry from a URL or a path to a local jorfile
+    pub fn new(name: String, location: &str) -> Result<Self, Error> {
+        if !crate::config::is_valid_name(&name) || name == UPSTREAM || name == crate::common::LOCAL
+        {
+            return Err(Error::InvalidName(name));
+        }
+
+        let url = if location.starts_with("http://")
+            || location.starts_with("https://")
+            || location.starts_with("file://")
+        {
+            location.to_owned()
+        } else {
+            let path = fs::canonicalize(location)
+                .map_err(|e| Error::InvalidPath(e, PathBuf::from(location)))?;
+            reqwest::Url::from_file_path(&path)
+                .map(|url| url.to_string())
+                .map_err(|()| Error::NotAFileUrl(path.clone()))?
+        };
+
+        Ok(Registry { name, url })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// a jorfile on the local file system, synced even when offline
+    pub fn is_local(&self) -> bool {
+        self.url.starts_with("file://")
+    }
+
+    /// where the registry's jorfile is cached
+    pub fn cache_file(&self, cache_dir: &Path) -> PathBuf {
+        cache_dir
+            .join("jorfiles")
+            .join(format!("{}.json", self.name))
+    }
+}
+
+impl Registries {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::CannotRead(err, path.to_path_buf())),
+        };
+        serde_json::from_slice(&content).map_err(|e| Error::Malformed(e, path.to_path_buf()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let content = serde_json::to_vec_pretty(self).unwrap();
+        fs::write(path, content).map_err(|e| Error::CannotWrite(e, path.to_path_buf()))
+    }
+
+    /// add a registry with a lower priority than the existing ones
+    pub fn add(&mut self, registry: Registry) -> Result<(), Error> {
+        if self.get(registry.name()).is_some() {
+            return Err(Error::AlreadyExists(registry.name));
+        }
+        self.0.push(registry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Registry, Error> {
+        let index = self
+            .0
+            .iter()
+            .position(|registry| registry.name() == name)
+            .ok_or_else(|| Error::NotFound(name.to_owned()))?;
+        Ok(self.0.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Registry> {
+        self.0.iter().find(|registry| registry.name() == name)
+    }
+
+    /// the registries, highest priority first
+    pub fn iter(&self) -> impl Iterator<Item = &Registry> {
+        self.0.iter()
+    }
+}
@@ -1,25 +1,72 @@
 use crate::{
     common::JorupConfig,
     utils::{
-        download::Client,
+        archive,
+        download::{self, Client},
         github,
+        manifest::{self, Manifest},
+        proxy,
+        release_source::ReleaseSource,
+        signature::{self, TrustedKeys},
+        target::{AssetKind, Target},
         version::{Version, VersionReq},
     },
 };
+use chrono::{Duration, Utc};
 use std::{
+    env::consts::EXE_SUFFIX,
+    fmt,
     fs::{self, File},
     io,
     path::{Path, PathBuf},
 };
 use thiserror::Error;
 
-const TARGET: &str = concat!(env!("TARGET"), "-generic");
+/// the binaries making a release
+const BINARIES: [&str; 2] = ["jormungandr", "jcli"];
+
+/// where releases installed before manifests were written keep their archive
+const LEGACY_ASSET_FILES: [&str; 2] = ["archive.tar.gz", "archive.zip"];
 
 pub struct Release {
     version: Version,
     path: PathBuf,
 }
 
+/// a release built or downloaded outside of jorup
+pub enum LocalSource<'a> {
+    /// a release archive as published on the release server
+    Archive(&'a Path),
+    /// a directory containing the `jormungandr` and `jcli` binaries
+    Directory(&'a Path),
+}
+
+/// the release asset for the current platform as published remotely
+pub struct RemoteAsset {
+    name: String,
+    url: String,
+    sha256: Option<String>,
+    signature: Option<String>,
+}
+
+/// the steps of an installation, to report which one failed
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Stage,
+    Unpack,
+    CheckBinaries,
+    DetectVersion,
+    WriteManifest,
+    Commit,
+}
+
+/// a release being assembled in a temporary directory next to the installed
+/// ones, removed unless committed
+struct Staging {
+    path: PathBuf,
+    committed: bool,
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Cannot read the release directory: {1}")]
@@ -30,16 +77,55 @@ pub enum Error {
     GitHub(#[from] crate::utils::github::Error),
     #[error("Error while opening file: {1}")]
     CannotOpenFile(#[source] io::Error, PathBuf),
-    #[error("Asset not found for the current platform")]
-    AssetNotFound,
-    #[cfg(unix)]
-    #[error("Cannot unpack assset: {1}")]
-    CannotUnpack(#[source] io::Error, PathBuf),
-    #[cfg(windows)]
+    #[error("No release asset found for {target}, available assets: {}", .candidates.join(", "))]
+    AssetNotFound {
+        target: String,
+        candidates: Vec<String>,
+    },
     #[error("Cannot unpack assset: {1}")]
-    CannotUnpack(#[source] zip::result::ZipError, PathBuf),
+    CannotUnpack(#[source] archive::Error, PathBuf),
     #[error("Cannot set the release as default")]
     CannotSetDefault(#[source] io::Error),
+    #[error("Cannot fetch the checksum of asset {0}")]
+    CannotFetchChecksum(String, #[source] download::Error),
+    #[error("Cannot fetch the signature of asset {0}")]
+    CannotFetchSignature(String, #[source] download::Error),
+    #[error("Invalid signature for asset {1}")]
+    BadSignature(#[source] signature::Error, PathBuf),
+    #[error("Cannot install the release from {1}")]
+    CannotInstall(#[source] io::Error, PathBuf),
+    #[error("Missing binary {0}")]
+    MissingBinary(PathBuf),
+    #[error("Cannot detect the version of {0}, please specify it with `--version`")]
+    CannotDetectVersion(PathBuf),
+    #[error("Release {0} is already installed")]
+    AlreadyInstalled(Version),
+    #[error("Cannot access the release manifest")]
+    Manifest(#[source] manifest::Error),
+    #[error("{0} is not executable")]
+    NotExecutable(PathBuf),
+    #[error("Installation failed while {step}")]
+    Install {
+        step: Step,
+        #[source]
+        source: Box<Error>,
+    },
+    #[error("Cannot get the version reported by {0}")]
+    NoVersionOutput(PathBuf),
+    #[error("{binary} reports `{output}`, expecting version {expected}")]
+    VersionMismatch {
+        binary: PathBuf,
+        expected: Version,
+        output: String,
+    },
+    #[error("{0} is not a release directory named after its version")]
+    NotARelease(PathBuf),
+    #[error("Checksum mismatch for {asset}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        asset: PathBuf,
+        expected: String,
+        actual: String,
+    },
 }
 
 pub fn list_installed_releases(cfg: &JorupConfig) -> Result<Vec<Release>, Error> {
@@ -61,10 +147,42 @@ pub fn list_installed_releases(cfg: &JorupConfig) -> Result<Vec<Release>, Error>
                 .and_then(Result::ok)
         })
         .map(|version| Release::new_unchecked(cfg, version))
-        .filter(|release| !release.asset_need_fetched())
+        // releases installed before manifests were written have no manifest
+        .filter(|release| Manifest::exists(release.dir()) || !release.asset_need_open())
         .collect())
 }
 
+/// every entry of the release directory, including the broken ones skipped
+/// by [`list_installed_releases`], the ones that are not directories named
+/// after a version being reported as [`Error::NotARelease`]
+///
+/// The hidden download cache and staging directories are left out.
+pub fn list_release_dirs(cfg: &JorupConfig) -> Result<Vec<Result<Release, Error>>, Error> {
+    let entries = fs::read_dir(cfg.release_dir())
+        .map_err(|err| Error::ReleaseDirectory(err, cfg.release_dir()))?;
+
+    let mut releases = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|err| Error::ReleaseDirectory(err, cfg.release_dir()))?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let is_dir = entry
+            .file_type()
+            .map(|etype| etype.is_dir())
+            .unwrap_or(false);
+        releases.push(
+            name.to_str()
+                .and_then(|name| Version::parse(name).ok())
+                .filter(|_| is_dir)
+                .map(|version| Release::new_unchecked(cfg, version))
+                .ok_or_else(|| Error::NotARelease(entry.path())),
+        );
+    }
+    Ok(releases)
+}
+
 impl Release {
     /// load the latest locally installed release
     pub fn load(cfg: &JorupConfig, version_req: &VersionReq) -> Result<Self, Error> {
@@ -81,105 +199,260 @@ impl Release {
         Release { version, path }
     }
 
-    pub fn make_default(&self, cfg: &JorupConfig) -> Result<(), Error> {
-        let bin_dir = cfg.bin_dir();
-
-        let install_jormungandr = bin_dir.join("jormungandr");
-        let install_jcli = bin_dir.join("jcli");
-
-        // remove old symlinks
-        if install_jormungandr.exists() {
-            std::fs::remove_file(&install_jormungandr).map_err(Error::CannotSetDefault)?;
-        }
-        if install_jcli.exists() {
-            std::fs::remove_file(&install_jcli).map_err(Error::CannotSetDefault)?;
-        }
+    /// the release run by the `jormungandr` and `jcli` proxies by default,
+    /// if any
+    pub fn current_default(cfg: &JorupConfig) -> Option<Self> {
+        let version = match fs::read_to_string(cfg.default_release_file()) {
+            Ok(version) => version.trim().parse().ok()?,
+            // installations made before the proxies symlink the binaries
+            Err(_) => {
+                let jormungandr = fs::read_link(cfg.bin_dir().join("jormungandr")).ok()?;
+                jormungandr.parent()?.file_name()?.to_str()?.parse().ok()?
+            }
+        };
+        Some(Release::new_unchecked(cfg, version))
+    }
 
-        create_symlink(self.get_jormungandr(), install_jormungandr)
-            .map_err(Error::CannotSetDefault)?;
-        create_symlink(self.get_jcli(), install_jcli).map_err(Error::CannotSetDefault)?;
+    pub fn make_default(&self, cfg: &JorupConfig) -> Result<(), Error> {
+        self.record_default(cfg).map_err(Error::CannotSetDefault)?;
+        proxy::install(cfg).map_err(Error::CannotSetDefault)
+    }
 
-        Ok(())
+    /// record the release as the default one run by the proxies, without
+    /// installing them
+    pub fn record_default(&self, cfg: &JorupConfig) -> io::Result<()> {
+        fs::write(cfg.default_release_file(), format!("{}\n", self.version))
     }
 
     pub fn get_jormungandr(&self) -> PathBuf {
-        self.dir().join("jormungandr")
+        self.dir().join(binary_file("jormungandr"))
     }
 
     pub fn get_jcli(&self) -> PathBuf {
-        self.dir().join("jcli")
+        self.dir().join(binary_file("jcli"))
     }
 
-    #[cfg(windows)]
-    pub fn get_asset(&self) -> PathBuf {
-        self.dir().join("archive.zip")
+    /// where the release archive is downloaded before being installed,
+    /// kept between runs so an interrupted download can be resumed
+    pub fn download_path(&self, cfg: &JorupConfig, asset_name: &str) -> PathBuf {
+        cfg.release_dir()
+            .join(".downloads")
+            .join(self.version.to_string())
+            .join(asset_name)
     }
 
-    #[cfg(unix)]
-    pub fn get_asset(&self) -> PathBuf {
-        self.dir().join("archive.tar.gz")
+    pub fn asset_need_open(&self) -> bool {
+        !self.get_jormungandr().is_file() || !self.get_jcli().is_file()
     }
 
-    pub fn asset_need_fetched(&self) -> bool {
-        !self.get_asset().is_file()
+    /// install a release from an archive or a directory holding the binaries
+    ///
+    /// The release is assembled in a staging directory: the binaries are
+    /// unpacked or copied and checked, the version is taken from `version`
+    /// or detected by running `jormungandr --version`, and the manifest is
+    /// written recording `origin`. The staging directory is then moved into
+    /// place in one step, replacing a broken installation of the same
+    /// version if `replace` is set. Any failure leaves the installed
+    /// releases untouched.
+    pub fn install(
+        cfg: &JorupConfig,
+        source: LocalSource,
+        version: Option<Version>,
+        origin: String,
+        target: &Target,
+        replace: bool,
+    ) -> Result<Self, Error> {
+        let staging = in_step(Step::Stage, Staging::new(cfg))?;
+        let staged = staging.path();
+
+        in_step(Step::Unpack, stage_binaries(&source, staged))?;
+        in_step(Step::CheckBinaries, check_binaries(staged))?;
+
+        let version = match version {
+            Some(version) => version,
+            None => in_step(
+                Step::DetectVersion,
+                detect_version(&staged.join(binary_file("jormungandr")))
+                    .or_else(|| match source {
+                        LocalSource::Archive(archive) => version_from_file_name(archive),
+                        LocalSource::Directory(_) => None,
+                    })
+                    .ok_or_else(|| {
+                        Error::CannotDetectVersion(staged.join(binary_file("jormungandr")))
+                    }),
+            )?,
+        };
+
+        let release = Release::new_unchecked(cfg, version);
+        if !replace && release.dir().exists() {
+            return Err(Error::AlreadyInstalled(release.version));
+        }
+
+        in_step(
+            Step::WriteManifest,
+            write_manifest(&source, staged, origin, target),
+        )?;
+        in_step(Step::Commit, staging.commit(release.dir()))?;
+
+        Ok(release)
     }
 
-    pub fn asset_need_open(&self) -> bool {
-        !self.get_jormungandr().is_file() || !self.get_jcli().is_file()
+    pub fn manifest(&self) -> Result<Option<Manifest>, Error> {
+        Manifest::load(self.dir()).map_err(Error::Manifest)
     }
 
-    pub fn asset_open(&self) -> Result<(), Error> {
-        if !self.asset_need_open() {
-            return Ok(());
-        }
-        let file =
-            File::open(self.get_asset()).map_err(|e| Error::CannotOpenFile(e, self.get_asset()))?;
-        self.unpack_asset(file)
-    }
-
-    #[cfg(windows)]
-    fn unpack_asset(&self, file: File) -> Result<(), Error> {
-        let mut archive = zip::read::ZipArchive::new(file)
-            .map_err(|e| Error::CannotUnpack(e, self.get_asset()))?;
-        for i in 0..archive.len() {
-            let mut file = archive
-                .by_index(i)
-                .map_err(|e| Error::CannotUnpack(e, self.get_asset()))?;
-            let path = self.dir().join(file.name());
-            let mut decompressed_file = File::create(path.clone())
-                .map_err(|e| Error::CannotOpenFile(e, path.to_path_buf()))?;
-            std::io::copy(&mut file, &mut decompressed_file)
-                .map_err(|e| Error::CannotOpenFile(e, path.to_path_buf()))?;
+    /// record that the release is being run, for `jorup node prune`
+    ///
+    /// This is best effort and done at most once an hour, so the proxies do
+    /// not rewrite the manifest on every call.
+    pub fn mark_used(&self) {
+        if let Ok(Some(mut manifest)) = self.manifest() {
+            if Utc::now() - *manifest.last_used() > Duration::hours(1) {
+                manifest.mark_used();
+                let _ = manifest.save(self.dir());
+            }
         }
+    }
 
-        Ok(())
+    /// the disk space taken by the release directory, in bytes
+    pub fn disk_usage(&self) -> u64 {
+        dir_size(self.dir())
     }
 
-    #[cfg(unix)]
-    fn unpack_asset(&self, file: File) -> Result<(), Error> {
-        use flate2::read::GzDecoder;
-        use tar::Archive;
+    /// the release archive kept in the release directory, if any
+    pub fn archive(&self) -> Option<PathBuf> {
+        let recorded = self
+            .manifest()
+            .ok()
+            .flatten()
+            .and_then(|manifest| manifest.asset().map(|asset| self.dir().join(asset)));
+        recorded.or_else(|| {
+            LEGACY_ASSET_FILES
+                .iter()
+                .map(|file| self.dir().join(file))
+                .find(|path| path.is_file())
+        })
+    }
 
-        let content = GzDecoder::new(file);
-        let mut archive = Archive::new(content);
-        archive.set_preserve_permissions(true);
-        archive
-            .unpack(self.dir())
-            .map_err(|e| Error::CannotUnpack(e, self.get_asset()))?;
+    /// check the integrity of an installed release, returning every problem
+    /// found
+    ///
+    /// The archive is checked against the digest recorded in the manifest
+    /// and against `upstream` if given, the binaries must be present and
+    /// executable and report the version of the release. Nightly builds do
+    /// not report their date so only their presence is checked.
+    pub fn verify(&self, upstream: Option<&str>) -> Vec<Error> {
+        let mut problems = Vec::new();
 
-        Ok(())
+        let manifest = match self.manifest() {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                problems.push(err);
+                None
+            }
+        };
+        let expected: Vec<&str> = manifest
+            .as_ref()
+            .and_then(Manifest::sha256)
+            .into_iter()
+            .chain(upstream)
+            .collect();
+        match self.archive() {
+            Some(archive) if !expected.is_empty() => match sha256_file(archive.clone()) {
+                Ok(actual) => problems.extend(
+                    expected
+                        .iter()
+                        .filter(|expected| !expected.eq_ignore_ascii_case(&actual))
+                        .map(|expected| Error::ChecksumMismatch {
+                            asset: archive.clone(),
+                            expected: (*expected).to_owned(),
+                            actual: actual.clone(),
+                        }),
+                ),
+                Err(err) => problems.push(err),
+            },
+            _ => (),
+        }
+
+        for binary in BINARIES.iter().copied().map(binary_file) {
+            let path = self.dir().join(binary);
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => {
+                    if !is_executable(&metadata) {
+                        problems.push(Error::NotExecutable(path));
+                    } else if let Version::Stable(expected) = &self.version {
+                        let output = match version_output(&path) {
+                            Some(output) => output,
+                            None => {
+                                problems.push(Error::NoVersionOutput(path));
+                                continue;
+                            }
+                        };
+                        let reported = output
+                            .split_whitespace()
+                            .nth(1)
+                            .and_then(|version| Version::from_git_tag(version).ok());
+                        // build metadata (the commit of source builds) is
+                        // not compared
+                        if !matches!(reported, Some(Version::Stable(reported)) if &reported == expected)
+                        {
+                            problems.push(Error::VersionMismatch {
+                                binary: path,
+                                expected: self.version.clone(),
+                                output,
+                            });
+                        }
+                    }
+                }
+                _ => problems.push(Error::MissingBinary(path)),
+            }
+        }
+
+        problems
     }
 
-    pub fn asset_remote(&self, client: &mut Client) -> Result<String, Error> {
+    pub fn asset_remote(
+        &self,
+        source: &dyn ReleaseSource,
+        client: &mut Client,
+        target: &Target,
+    ) -> Result<RemoteAsset, Error> {
         let release = github::find_matching_release(
+            source,
             client,
             github::JORMUNGANDR,
             VersionReq::exact(self.version.clone()),
         )?;
-        match release.get_asset_url(TARGET) {
-            Some(url) => Ok(url.to_string()),
-            None => Err(Error::AssetNotFound),
-        }
+        let asset = release
+            .get_asset(target, AssetKind::Archive)
+            .ok_or_else(|| Error::AssetNotFound {
+                target: target.to_string(),
+                candidates: release.asset_names(),
+            })?;
+
+        let sha256 = match release.get_checksum_asset(asset) {
+            Some(checksum_asset) => {
+                let content = fetch_text(client, checksum_asset)
+                    .map_err(|e| Error::CannotFetchChecksum(asset.name().to_owned(), e))?;
+                github::parse_checksum(&content, asset.name())
+            }
+            None => None,
+        };
+
+        let signature = match release.get_signature_asset(asset) {
+            Some(signature_asset) => Some(
+                fetch_text(client, signature_asset)
+                    .map_err(|e| Error::CannotFetchSignature(asset.name().to_owned(), e))?,
+            ),
+            None => None,
+        };
+
+        Ok(RemoteAsset {
+            name: asset.name().to_owned(),
+            url: asset.url().to_owned(),
+            sha256,
+            signature,
+        })
     }
 
     pub fn dir(&self) -> &PathBuf {
@@ -191,12 +464,325 @@ impl Release {
     }
 }
 
+impl Staging {
+    fn new(cfg: &JorupConfig) -> Result<Self, Error> {
+        let path = cfg
+            .release_dir()
+            .join(format!(".staging-{}", std::process::id()));
+        if path.exists() {
+            fs::remove_dir_all(&path).map_err(|e| Error::CannotInstall(e, path.clone()))?;
+        }
+        fs::create_dir_all(&path).map_err(|e| Error::CannotInstall(e, path.clone()))?;
+        Ok(Staging {
+            path,
+            committed: false,
+        })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// move the staged release to `dir`, putting back what was there if
+    /// the move fails
+    fn commit(mut self, dir: &Path) -> Result<(), Error> {
+        let previous = self.path.with_extension("previous");
+        let replacing = dir.exists();
+        if replacing {
+            fs::rename(dir, &previous).map_err(|e| Error::CannotInstall(e, dir.to_path_buf()))?;
+        }
+
+        if let Err(err) = fs::rename(&self.path, dir) {
+            if replacing {
+                let _ = fs::rename(&previous, dir);
+            }
+            return Err(Error::CannotInstall(err, dir.to_path_buf()));
+        }
+        self.committed = true;
+
+        if replacing {
+            if let Err(err) = fs::remove_dir_all(&previous) {
+                eprintln!(
+                    "WARN: cannot remove the replaced release {}: {}",
+                    previous.display(),
+                    err
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Step::Stage => "preparing the staging directory",
+            Step::Unpack => "unpacking the binaries",
+            Step::CheckBinaries => "checking the binaries",
+            Step::DetectVersion => "detecting the version",
+            Step::WriteManifest => "writing the manifest",
+            Step::Commit => "moving the release into place",
+        })
+    }
+}
+
+fn in_step<T>(step: Step, res: Result<T, Error>) -> Result<T, Error> {
+    res.map_err(|source| Error::Install {
+        step,
+        source: Box::new(source),
+    })
+}
+
+fn stage_binaries(source: &LocalSource, staging: &Path) -> Result<(), Error> {
+    match source {
+        LocalSource::Archive(archive) => {
+            archive::unpack(archive, staging)
+                .map_err(|e| Error::CannotUnpack(e, archive.to_path_buf()))?;
+            for binary in BINARIES.iter().copied().map(binary_file) {
+                let path = staging.join(&binary);
+                if path.is_file() {
+                    continue;
+                }
+                // the binaries may be under a top-level directory
+                let found = archive::find_file(staging, &binary)
+                    .map_err(|e| Error::CannotInstall(e, staging.to_path_buf()))?
+                    .ok_or_else(|| Error::MissingBinary(PathBuf::from(&binary)))?;
+                fs::rename(&found, &path).map_err(|e| Error::CannotInstall(e, found.clone()))?;
+                // drop the directories left empty
+                let mut dir = found.parent();
+                while let Some(parent) = dir.filter(|dir| *dir != staging) {
+                    if fs::remove_dir(parent).is_err() {
+                        break;
+                    }
+                    dir = parent.parent();
+                }
+            }
+            let asset = staging.join(archive_file(archive));
+            fs::copy(archive, &asset).map_err(|e| Error::CannotInstall(e, asset))?;
+        }
+        LocalSource::Directory(dir) => {
+            for binary in BINARIES.iter().copied().map(binary_file) {
+                let from = dir.join(&binary);
+                if !from.is_file() {
+                    return Err(Error::MissingBinary(from));
+                }
+                fs::copy(&from, staging.join(binary)).map_err(|e| Error::CannotInstall(e, from))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_binaries(dir: &Path) -> Result<(), Error> {
+    for binary in BINARIES.iter().copied().map(binary_file) {
+        let path = dir.join(binary);
+        let metadata = fs::metadata(&path).map_err(|_| Error::MissingBinary(path.clone()))?;
+        if !metadata.is_file() {
+            return Err(Error::MissingBinary(path));
+        }
+        if !is_executable(&metadata) {
+            return Err(Error::NotExecutable(path));
+        }
+    }
+    Ok(())
+}
+
+/// the file name of a binary on this platform
+fn binary_file(name: &str) -> String {
+    format!("{}{}", name, EXE_SUFFIX)
+}
+
+/// the release archive is kept in the release directory under its own name
+fn archive_file(archive: &Path) -> String {
+    archive
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archive".to_owned())
+}
+
 #[cfg(unix)]
-fn create_symlink<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
-    std::os::unix::fs::symlink(src, dst)
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+    metadata.permissions().mode() & 0o111 != 0
 }
 
 #[cfg(windows)]
-fn create_symlink<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
-    std::os::windows::fs::symlink_file(src, dst)
+fn is_executable(_: &fs::Metadata) -> bool {
+    true
+}
+
+fn write_manifest(
+    source: &LocalSource,
+    staging: &Path,
+    origin: String,
+    target: &Target,
+) -> Result<(), Error> {
+    let mut manifest = Manifest::new(origin, target).with_versions(
+        version_output(&staging.join(binary_file("jormungandr"))),
+        version_output(&staging.join(binary_file("jcli"))),
+    );
+    if let LocalSource::Archive(archive) = source {
+        let name = archive_file(archive);
+        let asset = staging.join(&name);
+        let size = fs::metadata(&asset)
+            .map_err(|e| Error::CannotOpenFile(e, asset.clone()))?
+            .len();
+        manifest = manifest.with_asset(name, sha256_file(asset)?, size);
+    }
+    manifest.save(staging).map_err(Error::Manifest)
+}
+
+/// check a downloaded archive against the expected SHA-256 digests
+///
+/// On mismatch the archive is removed so a corrupted or tampered download is
+/// never unpacked nor reused by a later install.
+pub fn verify_checksum<S: AsRef<str>>(archive: &Path, expected: &[S]) -> Result<(), Error> {
+    let actual = sha256_file(archive.to_path_buf())?;
+
+    let mismatch = expected
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|expected| !expected.eq_ignore_ascii_case(&actual));
+
+    if let Some(expected) = mismatch {
+        discard(archive);
+        return Err(Error::ChecksumMismatch {
+            asset: archive.to_path_buf(),
+            expected: expected.to_owned(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// check a downloaded archive against its detached minisign signature,
+/// removing it if the signature does not match
+pub fn verify_signature(archive: &Path, keys: &TrustedKeys, signature: &str) -> Result<(), Error> {
+    keys.verify(archive, signature).map_err(|e| {
+        discard(archive);
+        Error::BadSignature(e, archive.to_path_buf())
+    })
+}
+
+fn discard(archive: &Path) {
+    if let Err(err) = fs::remove_file(archive) {
+        eprintln!(
+            "WARN: cannot remove the download {}: {}",
+            archive.display(),
+            err
+        );
+    }
+}
+
+impl RemoteAsset {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// the SHA-256 digest published alongside the asset, if any
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// the content of the detached minisign signature of the asset, if any
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+}
+
+/// download a small auxiliary asset (checksum or signature) in memory
+pub fn fetch_text(client: &mut Client, asset: &github::Asset) -> Result<String, download::Error> {
+    let content = client.fetch_metadata(asset.name(), asset.url())?;
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// the version reported by `jormungandr --version` (`jormungandr 0.9.0 ...`)
+fn detect_version(jormungandr: &Path) -> Option<Version> {
+    let output = version_output(jormungandr)?;
+    let version = output.split_whitespace().nth(1)?;
+    Version::from_git_tag(version).ok()
+}
+
+/// what the binary prints with `--version`
+fn version_output(binary: &Path) -> Option<String> {
+    let output = std::process::Command::new(binary)
+        .arg("--version")
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned())
+}
+
+/// the version from an archive named like the published assets, for example
+/// `jormungandr-v0.9.0-x86_64-unknown-linux-gnu-generic.tar.gz`
+fn version_from_file_name(archive: &Path) -> Option<Version> {
+    let name = archive.file_name()?.to_str()?;
+    let version = name.strip_prefix("jormungandr-")?.split('-').next()?;
+    Version::from_git_tag(version).ok()
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+fn sha256_file(path: PathBuf) -> Result<String, Error> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = File::open(&path).map_err(|e| Error::CannotOpenFile(e, path.clone()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| Error::CannotOpenFile(e, path))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn default_from_symlinks_recorded() {
+        let home = tempfile::tempdir().unwrap();
+        let cfg = JorupConfig::new(Some(home.path().to_path_buf()), None, true, None).unwrap();
+
+        // the layout of installations made before the proxies
+        let release = cfg.release_dir().join("0.9.0");
+        fs::create_dir_all(&release).unwrap();
+        fs::create_dir_all(cfg.bin_dir()).unwrap();
+        std::os::unix::fs::symlink(
+            release.join("jormungandr"),
+            cfg.bin_dir().join("jormungandr"),
+        )
+        .unwrap();
+
+        let default = Release::current_default(&cfg).unwrap();
+        assert_eq!(default.version().to_string(), "0.9.0");
+        default.record_default(&cfg).unwrap();
+
+        fs::remove_file(cfg.bin_dir().join("jormungandr")).unwrap();
+        let default = Release::current_default(&cfg).unwrap();
+        assert_eq!(default.version().to_string(), "0.9.0");
+    }
 }
@@ -0,0 +1,238 @@
+use crate::utils::{
+    download::{self, Client},
+    github::ReleaseDef,
+};
+use reqwest::Url;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// where release metadata (and the assets they point to) are fetched from
+pub trait ReleaseSource: fmt::Debug {
+    /// the latest stable release of `repo`
+    fn latest(&self, client: &mut Client, repo: &str) -> Result<ReleaseDef, Error>;
+
+    /// the release of `repo` with the given git tag
+    fn by_tag(&self, client: &mut Client, repo: &str, tag: &str) -> Result<ReleaseDef, Error>;
+
+    /// one page of the releases of `repo`, newest first
+    ///
+    /// Pages are numbered from 1, an empty page marks the end of the list.
+    fn releases_page(
+        &self,
+        client: &mut Client,
+        repo: &str,
+        page: u32,
+    ) -> Result<Vec<ReleaseDef>, Error>;
+}
+
+/// number of releases requested per page from the GitHub API (the maximum
+/// allowed)
+const GITHUB_PER_PAGE: u32 = 100;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to fetch releases")]
+    CannotGetReleaseData(#[from] download::Error),
+    #[error("Cannot parse the release data")]
+    MalformedReleaseData(#[from] serde_json::Error),
+    #[error("Cannot read the release index: {1}")]
+    CannotReadIndex(#[source] io::Error, PathBuf),
+    #[error("No release tagged {0}")]
+    TagNotFound(String),
+    #[error("No stable release")]
+    NoStableRelease,
+    #[error("Invalid release source: {0}")]
+    InvalidSource(String),
+    #[error("Cannot resolve the relative release source {1}")]
+    CurrentDir(#[source] io::Error, PathBuf),
+}
+
+/// the input-output-hk organisation on GitHub
+#[derive(Debug)]
+pub struct GitHub;
+
+/// a static mirror of the releases served over HTTP(S)
+///
+/// The mirror exposes `<base>/<repo>/releases.json` with the same content as
+/// the GitHub API `/releases` endpoint. Relative asset URLs are resolved
+/// against the index location.
+#[derive(Debug)]
+pub struct Mirror {
+    base: Url,
+}
+
+/// a local directory laid out like a [`Mirror`], for offline installs
+#[derive(Debug)]
+pub struct LocalDirectory {
+    path: PathBuf,
+}
+
+/// parse a release source from the command line or the settings
+///
+/// `github` selects the GitHub API, an `http://` or `https://` URL selects a
+/// mirror and anything else is taken as a path to a local directory.
+pub fn from_spec(spec: &str) -> Result<Box<dyn ReleaseSource>, Error> {
+    if spec == "github" {
+        return Ok(Box::new(GitHub));
+    }
+
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        return Mirror::new(spec).map(|mirror| Box::new(mirror) as Box<dyn ReleaseSource>);
+    }
+
+    let path = match spec.strip_prefix("file://") {
+        Some(path) => Path::new(path),
+        None => Path::new(spec),
+    };
+    LocalDirectory::new(path).map(|local| Box::new(local) as Box<dyn ReleaseSource>)
+}
+
+impl GitHub {
+    fn download(&self, client: &mut Client, url: &str) -> Result<Vec<u8>, Error> {
+        client
+            .fetch_metadata("GitHub release", url)
+            .map_err(Into::into)
+    }
+}
+
+impl ReleaseSource for GitHub {
+    fn latest(&self, client: &mut Client, repo: &str) -> Result<ReleaseDef, Error> {
+        let url = format!(
+            "https://api.github.com/repos/input-output-hk/{}/releases/latest",
+            repo
+        );
+        serde_json::from_slice(&self.download(client, &url)?).map_err(Into::into)
+    }
+
+    fn by_tag(&self, client: &mut Client, repo: &str, tag: &str) -> Result<ReleaseDef, Error> {
+        let url = format!(
+            "https://api.github.com/repos/input-output-hk/{}/releases/tags/{}",
+            repo, tag
+        );
+        serde_json::from_slice(&self.download(client, &url)?).map_err(Into::into)
+    }
+
+    fn releases_page(
+        &self,
+        client: &mut Client,
+        repo: &str,
+        page: u32,
+    ) -> Result<Vec<ReleaseDef>, Error> {
+        let url = format!(
+            "https://api.github.com/repos/input-output-hk/{}/releases?per_page={}&page={}",
+            repo, GITHUB_PER_PAGE, page
+        );
+        serde_json::from_slice(&self.download(client, &url)?).map_err(Into::into)
+    }
+}
+
+impl Mirror {
+    pub fn new(base: &str) -> Result<Self, Error> {
+        // make sure the last path segment is kept when joining
+        let base = if base.ends_with('/') {
+            base.to_owned()
+        } else {
+            format!("{}/", base)
+        };
+        let base = Url::parse(&base).map_err(|_| Error::InvalidSource(base.clone()))?;
+        Ok(Self { base })
+    }
+
+    fn index_url(&self, repo: &str) -> Result<Url, Error> {
+        self.base
+            .join(&format!("{}/releases.json", repo))
+            .map_err(|_| Error::InvalidSource(self.base.to_string()))
+    }
+}
+
+impl ReleaseSource for Mirror {
+    fn latest(&self, client: &mut Client, repo: &str) -> Result<ReleaseDef, Error> {
+        find_latest(self.releases_page(client, repo, 1)?)
+    }
+
+    fn by_tag(&self, client: &mut Client, repo: &str, tag: &str) -> Result<ReleaseDef, Error> {
+        find_tag(self.releases_page(client, repo, 1)?, tag)
+    }
+
+    fn releases_page(
+        &self,
+        client: &mut Client,
+        repo: &str,
+        page: u32,
+    ) -> Result<Vec<ReleaseDef>, Error> {
+        // the whole index is served at once
+        if page > 1 {
+            return Ok(Vec::new());
+        }
+
+        let url = self.index_url(repo)?;
+        let data = client.fetch_metadata("release index", url.as_str())?;
+        parse_index(&data, &url)
+    }
+}
+
+impl LocalDirectory {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()
+                .map_err(|e| Error::CurrentDir(e, path.to_path_buf()))?
+                .join(path)
+        };
+        Ok(Self { path })
+    }
+}
+
+impl ReleaseSource for LocalDirectory {
+    fn latest(&self, client: &mut Client, repo: &str) -> Result<ReleaseDef, Error> {
+        find_latest(self.releases_page(client, repo, 1)?)
+    }
+
+    fn by_tag(&self, client: &mut Client, repo: &str, tag: &str) -> Result<ReleaseDef, Error> {
+        find_tag(self.releases_page(client, repo, 1)?, tag)
+    }
+
+    fn releases_page(
+        &self,
+        _client: &mut Client,
+        repo: &str,
+        page: u32,
+    ) -> Result<Vec<ReleaseDef>, Error> {
+        if page > 1 {
+            return Ok(Vec::new());
+        }
+
+        let index = self.path.join(repo).join("releases.json");
+        let data = fs::read(&index).map_err(|e| Error::CannotReadIndex(e, index.clone()))?;
+        let url = Url::from_file_path(&index)
+            .map_err(|()| Error::InvalidSource(index.display().to_string()))?;
+        parse_index(&data, &url)
+    }
+}
+
+fn parse_index(data: &[u8], url: &Url) -> Result<Vec<ReleaseDef>, Error> {
+    let releases: Vec<ReleaseDef> = serde_json::from_slice(data)?;
+    Ok(releases
+        .into_iter()
+        .map(|release| release.rebase_assets(url))
+        .collect())
+}
+
+fn find_latest(releases: Vec<ReleaseDef>) -> Result<ReleaseDef, Error> {
+    releases
+        .into_iter()
+        .find(|release| !release.is_draft() && !release.is_prerelease())
+        .ok_or(Error::NoStableRelease)
+}
+
+fn find_tag(releases: Vec<ReleaseDef>, tag: &str) -> Result<ReleaseDef, Error> {
+    releases
+        .into_iter()
+        .find(|release| release.tag_name() == tag)
+        .ok_or_else(|| Error::TagNotFound(tag.to_owned()))
+}
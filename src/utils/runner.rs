@@ -1,13 +1,22 @@
-use crate::utils::blockchain::Blockchain;
+use crate::{
+    common::JorupConfig,
+    utils::{
+        blockchain::Blockchain,
+        lock::{self, Lock},
+    },
+};
 use serde::{Deserialize, Serialize};
 use std::{
-    io,
+    fs, io,
     net::SocketAddr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Child, Command, Stdio},
 };
 use thiserror::Error;
 
+/// the file in the blockchain directory describing the node started by jorup
+pub const RUNNER_FILE: &str = "running_config.json";
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct RunnerInfo {
@@ -19,6 +28,8 @@ pub struct RunnerInfo {
 
 pub struct RunnerControl<'a> {
     blockchain: &'a Blockchain,
+    /// held from checking that no node runs until the new one is recorded
+    lock: Option<Lock>,
     info: Option<RunnerInfo>,
     jcli: PathBuf,
     jormungandr: PathBuf,
@@ -52,10 +63,57 @@ pub enum Error {
     CannotSendStopSignal(#[source] io::Error),
     #[error("REST is not running")]
     RestNotRunning,
+    #[error("Cannot lock the node")]
+    Lock(#[source] lock::Error),
+}
+
+impl RunnerInfo {
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn jormungandr(&self) -> &Path {
+        &self.jormungandr
+    }
+
+    /// whether the node runs binaries from the given directory
+    pub fn uses(&self, dir: &Path) -> bool {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        canonical(&self.jormungandr).starts_with(canonical(dir))
+            || canonical(&self.jcli).starts_with(canonical(dir))
+    }
+}
+
+/// the nodes started by jorup that are still running, with the name of
+/// their blockchain
+pub fn running_nodes(cfg: &JorupConfig) -> Result<Vec<(String, RunnerInfo)>, Error> {
+    let entries = match fs::read_dir(cfg.blockchain_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::CannotOpenFile(err, cfg.blockchain_dir())),
+    };
+
+    let mut nodes = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let info_file = entry.path().join(RUNNER_FILE);
+        if !info_file.is_file() {
+            continue;
+        }
+        let info = fs::read_to_string(&info_file)
+            .map_err(|e| Error::CannotOpenFile(e, info_file.clone()))?;
+        let info: RunnerInfo =
+            serde_json::from_str(&info).map_err(|e| Error::Json(e, info_file))?;
+        if check_pid(info.pid)? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            nodes.push((name, info));
+        }
+    }
+    Ok(nodes)
 }
 
 impl<'a> RunnerControl<'a> {
     pub fn new(blockchain: &'a Blockchain, bin_dir: PathBuf) -> Result<Self, Error> {
+        let lock = blockchain.lock().map_err(Error::Lock)?;
         let info_file = blockchain.get_runner_file();
 
         if info_file.is_file() {
@@ -82,6 +140,7 @@ impl<'a> RunnerControl<'a> {
 
         Ok(RunnerControl {
             blockchain,
+            lock: Some(lock),
             info: None,
             jcli: bin_dir.join("jcli"),
             jormungandr: bin_dir.join("jormungandr"),
@@ -111,6 +170,7 @@ impl<'a> RunnerControl<'a> {
 
         Ok(RunnerControl {
             blockchain,
+            lock: None,
             info: Some(info),
             jcli,
             jormungandr,
@@ -234,6 +294,8 @@ impl<'a> RunnerControl<'a> {
             Stdio::inherit(),
             Stdio::inherit(),
         )?;
+        // the node is recorded, others may start or stop it from now on
+        self.lock = None;
 
         child
             .wait()
@@ -242,10 +304,16 @@ impl<'a> RunnerControl<'a> {
     }
 
     pub fn shutdown(&mut self) -> Result<(), Error> {
-        let info = if let Some(info) = std::mem::replace(&mut self.info, None) {
-            info
-        } else {
-            return Ok(());
+        // a control created to start the node already holds the lock, taking
+        // it again through another file handle would wait for ourselves
+        let _lock = match self.lock {
+            Some(_) => None,
+            None => Some(self.blockchain.lock().map_err(Error::Lock)?),
+        };
+        let info = match std::mem::replace(&mut self.info, None) {
+            // the node may have been stopped while waiting for the lock
+            Some(info) if self.blockchain.get_runner_file().is_file() => info,
+            _ => return Ok(()),
         };
 
         let status = self
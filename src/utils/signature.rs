@@ -0,0 +1,185 @@
+use crate::common::JorupConfig;
+use minisign_verify::{PublicKey, Signature};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// the minisign public keys the jormungandr and jorup releases are signed
+/// with
+///
+/// Upstream does not publish a signing key yet. Until one is listed here, or
+/// given at build time or in the settings, signatures are not required.
+const UPSTREAM_KEYS: &[&str] = &[];
+
+/// additional minisign public keys compiled in at build time, separated by
+/// whitespace or commas (see `JORUP_TRUSTED_KEYS` in `build.rs`)
+const EXTRA_KEYS: Option<&str> = option_env!("JORUP_TRUSTED_KEYS");
+
+pub struct TrustedKeys(Vec<PublicKey>);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid trusted public key: {0}")]
+    InvalidPublicKey(String, #[source] minisign_verify::Error),
+    #[error("Invalid signature file for {0}")]
+    MalformedSignature(PathBuf, #[source] minisign_verify::Error),
+    #[error("No trusted public key can verify the signature of {0}")]
+    BadSignature(PathBuf),
+    #[error("Cannot read file: {1}")]
+    CannotReadFile(#[source] io::Error, PathBuf),
+}
+
+impl TrustedKeys {
+    /// load the keys from the settings file, or the upstream keys and the
+    /// ones given at build time if the settings do not override them
+    pub fn load(cfg: &JorupConfig) -> Result<Self, Error> {
+        let keys: Vec<&str> = match cfg.settings().trusted_keys() {
+            Some(keys) => keys.iter().map(String::as_str).collect(),
+            None => UPSTREAM_KEYS
+                .iter()
+                .copied()
+                .chain(
+                    EXTRA_KEYS
+                        .unwrap_or_default()
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|key| !key.is_empty()),
+                )
+                .collect(),
+        };
+
+        keys.into_iter()
+            .map(|key| {
+                PublicKey::from_base64(key).map_err(|e| Error::InvalidPublicKey(key.to_owned(), e))
+            })
+            .collect::<Result<_, _>>()
+            .map(TrustedKeys)
+    }
+
+    /// whether no key is trusted, in which case signatures cannot be
+    /// checked and are not required
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// verify the file at `path` against the content of a `.minisig` file
+    pub fn verify<P: AsRef<Path>>(&self, path: P, signature: &str) -> Result<(), Error> {
+        let path = path.as_ref();
+        let signature = Signature::decode(signature)
+            .map_err(|e| Error::MalformedSignature(path.to_path_buf(), e))?;
+
+        for key in &self.0 {
+            let verified = match key.verify_stream(&signature) {
+                Ok(mut verifier) => {
+                    let mut file = File::open(path)
+                        .map_err(|e| Error::CannotReadFile(e, path.to_path_buf()))?;
+                    let mut buffer = [0; 8192];
+                    loop {
+                        let read = file
+                            .read(&mut buffer)
+                            .map_err(|e| Error::CannotReadFile(e, path.to_path_buf()))?;
+                        if read == 0 {
+                            break;
+                        }
+                        verifier.update(&buffer[..read]);
+                    }
+                    verifier.finalize()
+                }
+                // signed with another key
+                Err(minisign_verify::Error::UnexpectedKeyId) => continue,
+                // legacy signatures (`minisign -l`) sign the content itself
+                // rather than its hash and cannot be streamed
+                Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+                    let content =
+                        fs::read(path).map_err(|e| Error::CannotReadFile(e, path.to_path_buf()))?;
+                    key.verify(&content, &signature, true)
+                }
+                Err(err) => Err(err),
+            };
+
+            if verified.is_ok() {
+                return Ok(());
+            }
+        }
+
+        Err(Error::BadSignature(path.to_path_buf()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const OTHER_KEY: &str = "RWS93KSSOlQukIslOXDeOPgzkBy1WBuVw8468RHF5NfQdsRmKABiMN6H";
+
+    /// signature of the content `test` by `KEY`
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    /// legacy signature of the content `test` by `KEY`
+    const LEGACY_SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==
+";
+
+    fn keys(keys: &[&str]) -> TrustedKeys {
+        TrustedKeys(
+            keys.iter()
+                .map(|key| PublicKey::from_base64(key).unwrap())
+                .collect(),
+        )
+    }
+
+    fn verify(keys: &TrustedKeys, content: &[u8], signature: &str) -> Result<(), Error> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test");
+        fs::write(&path, content).unwrap();
+        keys.verify(&path, signature)
+    }
+
+    #[test]
+    fn good_signature() {
+        assert!(verify(&keys(&[KEY]), b"test", SIGNATURE).is_ok());
+        assert!(verify(&keys(&[OTHER_KEY, KEY]), b"test", SIGNATURE).is_ok());
+    }
+
+    #[test]
+    fn legacy_signature() {
+        assert!(verify(&keys(&[KEY]), b"test", LEGACY_SIGNATURE).is_ok());
+        assert!(matches!(
+            verify(&keys(&[KEY]), b"Test", LEGACY_SIGNATURE),
+            Err(Error::BadSignature(_))
+        ));
+    }
+
+    #[test]
+    fn bad_signature() {
+        assert!(matches!(
+            verify(&keys(&[KEY]), b"Test", SIGNATURE),
+            Err(Error::BadSignature(_))
+        ));
+        assert!(matches!(
+            verify(&keys(&[KEY]), b"test", "not a signature"),
+            Err(Error::MalformedSignature(..))
+        ));
+    }
+
+    #[test]
+    fn signed_by_another_key() {
+        assert!(matches!(
+            verify(&keys(&[OTHER_KEY]), b"test", SIGNATURE),
+            Err(Error::BadSignature(_))
+        ));
+        assert!(matches!(
+            verify(&keys(&[]), b"test", SIGNATURE),
+            Err(Error::BadSignature(_))
+        ));
+    }
+}
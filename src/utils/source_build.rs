@@ -0,0 +1,144 @@
+use crate::{common::JorupConfig, utils::version::Version};
+use semver::Identifier;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+/// a jormungandr source tree to build the node and jcli from
+pub struct SourceTree {
+    path: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Cannot run `{0}`, is it installed?")]
+    CannotRun(String, #[source] io::Error),
+    #[error("`{0}` failed")]
+    CommandFailed(String),
+    #[error("No source tree at {0}")]
+    NoSourceTree(PathBuf),
+    #[error(
+        "Cannot detect the version of the built jormungandr, please specify it with `--version`"
+    )]
+    CannotDetectVersion,
+}
+
+impl SourceTree {
+    /// use an existing source tree, for example a local clone being worked on
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if !path.join("Cargo.toml").is_file() {
+            return Err(Error::NoSourceTree(path.to_path_buf()));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// clone (or update a previous clone of) the repository at `url` under
+    /// `$JORUP_HOME/src` and check out `rev`, or the default branch if none
+    pub fn checkout(cfg: &JorupConfig, url: &str, rev: Option<&str>) -> Result<Self, Error> {
+        use sha2::{Digest, Sha256};
+
+        // one clone per repository URL
+        let key = hex::encode(&Sha256::digest(url.as_bytes())[..8]);
+        let path = cfg.source_dir().join(key);
+
+        if path.join(".git").is_dir() {
+            run(git(&path).args(["fetch", "--tags", "--force", "origin"]))?;
+        } else {
+            run(Command::new("git").args(["clone", url]).arg(&path))?;
+        }
+
+        // fetching only updates the remote branches, a branch name must be
+        // resolved through them rather than the stale local branch
+        let rev = match rev {
+            None => "origin/HEAD".to_owned(),
+            Some(rev) if is_commit(&path, &format!("origin/{}", rev)) => format!("origin/{}", rev),
+            Some(rev) => rev.to_owned(),
+        };
+        run(git(&path).args(["checkout", "--detach", &rev]))?;
+        run(git(&path).args(["submodule", "update", "--init", "--recursive"]))?;
+
+        Ok(Self { path })
+    }
+
+    /// build `jormungandr` and `jcli` in release mode, returning the
+    /// directory holding the binaries
+    pub fn build(&self) -> Result<PathBuf, Error> {
+        let target_dir = self.path.join("target");
+        run(Command::new("cargo")
+            .current_dir(&self.path)
+            .args(["build", "--release", "-p", "jormungandr", "-p", "jcli"])
+            .arg("--target-dir")
+            .arg(&target_dir))?;
+        Ok(target_dir.join("release"))
+    }
+
+    /// the short hash of the checked out commit, if the tree is a git clone
+    pub fn commit(&self) -> Option<String> {
+        let output = git(&self.path)
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout)
+            .ok()
+            .map(|hash| hash.trim().to_owned())
+    }
+
+    /// the version of the built binaries (as reported by
+    /// `jormungandr --version`) with the commit hash as build metadata, for
+    /// example `0.9.0+1a2b3c4`
+    pub fn version(&self, bin_dir: &Path) -> Result<Version, Error> {
+        let output = Command::new(bin_dir.join("jormungandr"))
+            .arg("--version")
+            .output()
+            .map_err(|e| Error::CannotRun("jormungandr --version".to_owned(), e))?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let mut version = output
+            .split_whitespace()
+            .nth(1)
+            .and_then(|version| semver::Version::parse(version).ok())
+            .ok_or(Error::CannotDetectVersion)?;
+
+        if let Some(commit) = self.commit() {
+            version.build = vec![Identifier::AlphaNumeric(commit)];
+        }
+
+        Ok(Version::Stable(version))
+    }
+}
+
+fn git(path: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(path);
+    cmd
+}
+
+/// whether `rev` names a commit in the clone at `path`
+fn is_commit(path: &Path, rev: &str) -> bool {
+    git(path)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", rev))
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn run(cmd: &mut Command) -> Result<(), Error> {
+    let description = format!("{:?}", cmd);
+    let status = cmd
+        .status()
+        .map_err(|e| Error::CannotRun(description.clone(), e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::CommandFailed(description))
+    }
+}
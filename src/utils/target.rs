@@ -0,0 +1,289 @@
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// the kind of file expected as release asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    /// an archive holding the binaries (jormungandr releases)
+    Archive,
+    /// the executable itself (jorup releases)
+    Binary,
+}
+
+/// archive formats jorup can unpack, in order of preference
+#[cfg(unix)]
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".tar.xz", ".zip"];
+#[cfg(windows)]
+const ARCHIVE_EXTENSIONS: &[&str] = &[".zip", ".tar.gz", ".tgz", ".tar.xz"];
+
+/// a platform target triple (`<arch>-<vendor>-<os>[-<abi>]`), with the
+/// different spellings used in asset names brought back to the rust ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    triple: String,
+}
+
+/// the components of a target triple
+struct Components<'a> {
+    arch: &'a str,
+    vendor: Option<&'a str>,
+    os: &'a str,
+    abi: Option<&'a str>,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid target triple: {0}")]
+pub struct TargetError(String);
+
+impl Target {
+    /// the target jorup was built for
+    pub fn current() -> Self {
+        env!("TARGET").parse().unwrap()
+    }
+
+    /// how well an asset with the given name fits this target, `None` if it
+    /// is not meant for it
+    ///
+    /// The architecture and operating system must match, the ABI must be the
+    /// same or a compatible one (musl builds run on gnu systems), and the
+    /// file must be of the expected kind. Exact triples, matching vendors and
+    /// `generic` (CPU agnostic) builds are preferred.
+    pub fn score(&self, asset_name: &str, kind: AssetKind) -> Option<u32> {
+        let name = asset_name.to_ascii_lowercase();
+
+        let (stem, mut score) = match kind {
+            AssetKind::Archive => ARCHIVE_EXTENSIONS.iter().enumerate().find_map(|(i, ext)| {
+                let preference = (ARCHIVE_EXTENSIONS.len() - i) as u32;
+                name.strip_suffix(ext).map(|stem| (stem, preference))
+            })?,
+            AssetKind::Binary => match name.strip_suffix(".exe") {
+                Some(stem) => (stem, 1),
+                None if !is_archive(&name) => (name.as_str(), 1),
+                None => return None,
+            },
+        };
+
+        let tokens: Vec<&str> = stem.split('-').collect();
+        let target = self.components();
+
+        let arch = tokens.iter().find_map(|token| normalize_arch(token))?;
+        let os = tokens.iter().find_map(|token| normalize_os(token))?;
+        if arch != target.arch || os != target.os {
+            return None;
+        }
+        score += 40;
+
+        match tokens.iter().find_map(|token| normalize_abi(token)) {
+            None => {}
+            Some(abi) if Some(abi) == target.abi => score += 10,
+            Some(abi) if abi_compatible(target.abi, abi) => score += 2,
+            Some(_) => return None,
+        }
+
+        if let Some(vendor) = target.vendor {
+            if tokens.contains(&vendor) {
+                score += 5;
+            }
+        }
+
+        if stem.contains(&self.triple) {
+            score += 100;
+        }
+
+        if tokens.contains(&"generic") {
+            score += 1;
+        }
+
+        Some(score)
+    }
+
+    fn components(&self) -> Components<'_> {
+        let parts: Vec<&str> = self.triple.split('-').collect();
+        let (arch, vendor, os, abi) = match parts.as_slice() {
+            [arch, vendor, os, abi] => (*arch, Some(*vendor), *os, Some(*abi)),
+            [arch, vendor, os] => (*arch, Some(*vendor), *os, None),
+            [arch, os] => (*arch, None, *os, None),
+            _ => unreachable!("the triple is checked when parsed"),
+        };
+
+        Components {
+            arch: normalize_arch(arch).unwrap_or(arch),
+            vendor,
+            os: normalize_os(os).unwrap_or(os),
+            abi: abi.map(|abi| normalize_abi(abi).unwrap_or(abi)),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = TargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split('-').count();
+        if !(2..=4).contains(&parts) || s.split('-').any(str::is_empty) {
+            return Err(TargetError(s.to_owned()));
+        }
+
+        Ok(Target {
+            triple: s.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.triple)
+    }
+}
+
+fn normalize_arch(token: &str) -> Option<&'static str> {
+    match token {
+        "x86_64" | "amd64" | "x64" => Some("x86_64"),
+        "aarch64" | "arm64" => Some("aarch64"),
+        "i686" | "i586" | "i386" | "x86" => Some("i686"),
+        "armv7" | "armv7l" | "armv7hf" => Some("armv7"),
+        "arm" | "armv6" => Some("arm"),
+        _ => None,
+    }
+}
+
+fn normalize_os(token: &str) -> Option<&'static str> {
+    match token {
+        "linux" => Some("linux"),
+        "windows" | "win" | "win64" | "win32" => Some("windows"),
+        "darwin" | "macos" | "osx" => Some("darwin"),
+        "freebsd" => Some("freebsd"),
+        "android" | "androideabi" => Some("android"),
+        _ => None,
+    }
+}
+
+fn normalize_abi(token: &str) -> Option<&'static str> {
+    match token {
+        "gnu" => Some("gnu"),
+        "gnueabi" => Some("gnueabi"),
+        "gnueabihf" => Some("gnueabihf"),
+        "musl" => Some("musl"),
+        "musleabi" => Some("musleabi"),
+        "musleabihf" => Some("musleabihf"),
+        "msvc" => Some("msvc"),
+        _ => None,
+    }
+}
+
+/// whether binaries built for the `asset` ABI run on the `target` ABI
+fn abi_compatible(target: Option<&str>, asset: &str) -> bool {
+    matches!(
+        (target, asset),
+        // statically linked musl binaries run on any linux
+        (Some("gnu"), "musl")
+            | (Some("gnueabi"), "musleabi")
+            | (Some("gnueabihf"), "musleabihf")
+            | (Some("msvc"), "gnu")
+    )
+}
+
+fn is_archive(name: &str) -> bool {
+    [
+        ".tar.gz", ".tgz", ".tar.xz", ".zip", ".deb", ".rpm", ".msi", ".pkg",
+    ]
+    .iter()
+    .any(|ext| name.ends_with(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(target: &str, asset_name: &str) -> Option<u32> {
+        let target: Target = target.parse().unwrap();
+        target.score(asset_name, AssetKind::Archive)
+    }
+
+    #[test]
+    fn abi_aliases() {
+        for abi in &[
+            "gnu",
+            "gnueabi",
+            "gnueabihf",
+            "musl",
+            "musleabi",
+            "musleabihf",
+            "msvc",
+        ] {
+            assert_eq!(normalize_abi(abi), Some(*abi));
+        }
+        assert_eq!(normalize_abi("linux"), None);
+    }
+
+    #[test]
+    fn exact_triple_preferred() {
+        let target = "x86_64-unknown-linux-gnu";
+        let exact = score(target, "jormungandr-0.9.0-x86_64-unknown-linux-gnu.tar.gz").unwrap();
+        let alias = score(target, "jormungandr-0.9.0-linux-amd64-gnu.tar.gz").unwrap();
+        let musl = score(target, "jormungandr-0.9.0-x86_64-unknown-linux-musl.tar.gz").unwrap();
+        let generic = score(target, "jormungandr-0.9.0-linux-x64.tar.gz").unwrap();
+        assert!(exact > alias);
+        assert!(alias > musl);
+        assert!(musl > generic);
+    }
+
+    #[test]
+    fn other_platforms_rejected() {
+        let target = "x86_64-unknown-linux-gnu";
+        assert_eq!(
+            score(target, "jormungandr-0.9.0-aarch64-unknown-linux-gnu.tar.gz"),
+            None
+        );
+        assert_eq!(
+            score(target, "jormungandr-0.9.0-x86_64-apple-darwin.tar.gz"),
+            None
+        );
+        assert_eq!(
+            score(target, "jormungandr-0.9.0-x86_64-pc-windows-msvc.zip"),
+            None
+        );
+    }
+
+    #[test]
+    fn compatible_abis() {
+        // musl binaries run on gnu systems but not the other way around
+        assert!(score(
+            "x86_64-unknown-linux-musl",
+            "jormungandr-x86_64-unknown-linux-gnu.tar.gz"
+        )
+        .is_none());
+        assert!(score(
+            "armv7-unknown-linux-gnueabi",
+            "jormungandr-armv7-unknown-linux-musleabi.tar.gz"
+        )
+        .is_some());
+        assert!(score(
+            "armv7-unknown-linux-gnueabi",
+            "jormungandr-armv7-unknown-linux-gnueabihf.tar.gz"
+        )
+        .is_none());
+        assert!(score(
+            "armv7-unknown-linux-gnueabihf",
+            "jormungandr-armv7-unknown-linux-musleabihf.tar.gz"
+        )
+        .is_some());
+        assert!(score(
+            "x86_64-pc-windows-msvc",
+            "jormungandr-x86_64-pc-windows-gnu.zip"
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn asset_kinds() {
+        let target: Target = "x86_64-unknown-linux-gnu".parse().unwrap();
+        let binary = "jorup-0.7.0-x86_64-unknown-linux-gnu";
+        assert!(target.score(binary, AssetKind::Binary).is_some());
+        assert!(target.score(binary, AssetKind::Archive).is_none());
+        let archive = "jorup-0.7.0-x86_64-unknown-linux-gnu.tar.gz";
+        assert!(target.score(archive, AssetKind::Binary).is_none());
+        let package = "jormungandr-0.9.0-x86_64-unknown-linux-gnu.deb";
+        assert!(target.score(package, AssetKind::Archive).is_none());
+    }
+}
@@ -2,7 +2,7 @@ use chrono::{offset::Utc, Date};
 use semver::{
     ReqParseError, SemVerError, Version as SemverVersion, VersionReq as SemverVersionReq,
 };
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     cmp::{Ordering, PartialOrd},
     fmt,
@@ -21,11 +21,46 @@ pub enum Version {
 #[derive(Debug, Clone)]
 pub enum VersionReq {
     Latest,
+    /// the latest release, pre-releases included
+    Prerelease,
     Nightly,
+    /// the nightly built on the given day (`nightly.20200601`)
+    ExactNightly(Date<Utc>),
+    /// nightlies built within a range of days (`nightly>=20200601,<20200701`)
+    NightlyRange(Vec<DateBound>),
     Stable(SemverVersionReq),
     ExactStable(SemverVersion),
 }
 
+/// a comparison against the build date of a nightly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateBound {
+    op: DateOp,
+    date: Date<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOp {
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Eq,
+}
+
+/// the kind of releases a user follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    /// release candidates and other pre-releases (`0.9.0-rc1`)
+    Prerelease,
+    Nightly,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown channel {0}, expected stable, prerelease (or rc) or nightly")]
+pub struct ChannelError(String);
+
 #[derive(Debug, Error)]
 pub enum VersionError {
     #[error(transparent)]
@@ -34,25 +69,20 @@ pub enum VersionError {
     Nightly,
 }
 
+fn parse_date(date: &str) -> Option<Date<Utc>> {
+    chrono::NaiveDate::parse_from_str(date, DATEFMT)
+        .ok()
+        .map(|naive_date| Date::from_utc(naive_date, Utc))
+}
+
 impl Version {
     pub fn parse(version: &str) -> Result<Self, VersionError> {
         if version == "nightly" {
             return Ok(Version::Nightly(None));
         }
 
-        if version.starts_with("nightly") {
-            let mut parts = version.splitn(2, '.');
-            let date: u32 = parts
-                .nth(1)
-                .ok_or(VersionError::Nightly)?
-                .parse()
-                .map_err(|_| VersionError::Nightly)?;
-            let naive_date = chrono::NaiveDate::from_ymd(
-                (date / 10000) as i32,
-                (date % 10000 / 100) as u32,
-                (date % 100) as u32,
-            );
-            let date = Date::from_utc(naive_date, Utc);
+        if let Some(date) = version.strip_prefix("nightly.") {
+            let date = parse_date(date).ok_or(VersionError::Nightly)?;
             return Ok(Version::Nightly(Some(date)));
         }
 
@@ -68,18 +98,29 @@ impl Version {
 
     pub fn to_git_tag(&self) -> String {
         match self {
-            Version::Nightly(_) => "nightly".to_string(),
+            Version::Nightly(None) => "nightly".to_string(),
+            Version::Nightly(Some(_)) => self.to_string(),
             Version::Stable(version) => format!("v{}", version),
         }
     }
 
+    /// date an undated nightly (the rolling `nightly` tag) with its
+    /// publication day
     pub fn configure_nightly(self, date: Date<Utc>) -> Self {
         match self {
-            Version::Nightly(_) => Version::Nightly(Some(date)),
+            Version::Nightly(None) => Version::Nightly(Some(date)),
             v => v,
         }
     }
 
+    pub fn channel(&self) -> Channel {
+        match self {
+            Version::Nightly(_) => Channel::Nightly,
+            Version::Stable(version) if version.is_prerelease() => Channel::Prerelease,
+            Version::Stable(_) => Channel::Stable,
+        }
+    }
+
     pub fn get_nightly_date(&self) -> Option<&Date<Utc>> {
         if let Version::Nightly(maybe_date) = &self {
             maybe_date.as_ref()
@@ -95,6 +136,8 @@ pub enum VersionReqError {
     ReqError(#[from] ReqParseError),
     #[error(transparent)]
     VersionError(#[from] SemVerError),
+    #[error("invalid nightly requirement: {0}")]
+    Nightly(String),
 }
 
 impl VersionReq {
@@ -102,6 +145,19 @@ impl VersionReq {
         if version_req == "nightly" {
             return Ok(VersionReq::Nightly);
         }
+        if let Some(date) = version_req.strip_prefix("nightly.") {
+            return parse_date(date)
+                .map(VersionReq::ExactNightly)
+                .ok_or_else(|| VersionReqError::Nightly(version_req.to_owned()));
+        }
+        if let Some(bounds) = version_req.strip_prefix("nightly") {
+            return bounds
+                .split(',')
+                .map(|bound| DateBound::parse(bound.trim()))
+                .collect::<Option<Vec<_>>>()
+                .map(VersionReq::NightlyRange)
+                .ok_or_else(|| VersionReqError::Nightly(version_req.to_owned()));
+        }
         if version_req
             .chars()
             .next()
@@ -115,9 +171,37 @@ impl VersionReq {
             .map_err(Into::into)
     }
 
+    /// the latest release on the given channel
+    pub fn latest(channel: Channel) -> Self {
+        match channel {
+            Channel::Stable => VersionReq::Latest,
+            Channel::Prerelease => VersionReq::Prerelease,
+            Channel::Nightly => VersionReq::Nightly,
+        }
+    }
+
+    /// the channel of the releases this requirement is after, pre-releases
+    /// are only considered when asked for explicitly
+    pub fn channel(&self) -> Channel {
+        match self {
+            VersionReq::Latest => Channel::Stable,
+            VersionReq::Prerelease => Channel::Prerelease,
+            VersionReq::Nightly | VersionReq::ExactNightly(_) | VersionReq::NightlyRange(_) => {
+                Channel::Nightly
+            }
+            VersionReq::Stable(version_req) if version_req.to_string().contains('-') => {
+                Channel::Prerelease
+            }
+            VersionReq::Stable(_) => Channel::Stable,
+            VersionReq::ExactStable(version) if version.is_prerelease() => Channel::Prerelease,
+            VersionReq::ExactStable(_) => Channel::Stable,
+        }
+    }
+
     pub fn exact(version: Version) -> Self {
         match version {
-            Version::Nightly(_) => VersionReq::Nightly,
+            Version::Nightly(None) => VersionReq::Nightly,
+            Version::Nightly(Some(date)) => VersionReq::ExactNightly(date),
             Version::Stable(version) => VersionReq::ExactStable(version),
         }
     }
@@ -125,23 +209,65 @@ impl VersionReq {
     pub fn matches(&self, version: &Version) -> bool {
         match self {
             VersionReq::Latest => false,
+            VersionReq::Prerelease => matches!(version, Version::Stable(_)),
             VersionReq::Nightly => matches!(version, Version::Nightly(_)),
+            VersionReq::ExactNightly(date) => version.get_nightly_date() == Some(date),
+            VersionReq::NightlyRange(bounds) => match version.get_nightly_date() {
+                None => false,
+                Some(date) => bounds.iter().all(|bound| bound.matches(date)),
+            },
             VersionReq::Stable(version_req) => match version {
                 Version::Nightly(_) => false,
                 Version::Stable(version) => version_req.matches(version),
             },
             VersionReq::ExactStable(version_req) => match version {
                 Version::Nightly(_) => false,
-                Version::Stable(other) => version_req.eq(other),
+                // build metadata is ignored by semver equality but it is how
+                // releases built from source are told apart
+                Version::Stable(other) => {
+                    version_req.eq(other)
+                        && (version_req.build.is_empty() || version_req.build == other.build)
+                }
             },
         }
     }
 
     pub fn into_version(self) -> Option<Version> {
-        if let VersionReq::ExactStable(version) = self {
-            return Some(Version::Stable(version));
+        match self {
+            VersionReq::ExactStable(version) => Some(Version::Stable(version)),
+            VersionReq::ExactNightly(date) => Some(Version::Nightly(Some(date))),
+            _ => None,
+        }
+    }
+}
+
+impl DateBound {
+    fn parse(bound: &str) -> Option<Self> {
+        let (op, date) = if let Some(date) = bound.strip_prefix(">=") {
+            (DateOp::GreaterEq, date)
+        } else if let Some(date) = bound.strip_prefix("<=") {
+            (DateOp::LessEq, date)
+        } else if let Some(date) = bound.strip_prefix('>') {
+            (DateOp::Greater, date)
+        } else if let Some(date) = bound.strip_prefix('<') {
+            (DateOp::Less, date)
+        } else if let Some(date) = bound.strip_prefix('=') {
+            (DateOp::Eq, date)
+        } else {
+            return None;
+        };
+        let date = parse_date(date.trim())?;
+        Some(DateBound { op, date })
+    }
+
+    fn matches(&self, date: &Date<Utc>) -> bool {
+        match self.op {
+            DateOp::Greater => date > &self.date,
+            DateOp::GreaterEq => date >= &self.date,
+            DateOp::Less => date < &self.date,
+            DateOp::LessEq => date <= &self.date,
+            DateOp::Eq => date == &self.date,
         }
-        None
     }
 }
 
@@ -199,6 +325,15 @@ impl<'de> Deserialize<'de> for VersionReq {
     }
 }
 
+impl Serialize for VersionReq {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for Version {
     type Err = VersionError;
 
@@ -215,6 +350,29 @@ impl FromStr for VersionReq {
     }
 }
 
+impl FromStr for Channel {
+    type Err = ChannelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Channel::Stable),
+            "prerelease" | "rc" => Ok(Channel::Prerelease),
+            "nightly" => Ok(Channel::Nightly),
+            _ => Err(ChannelError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Stable => f.write_str("stable"),
+            Channel::Prerelease => f.write_str("prerelease"),
+            Channel::Nightly => f.write_str("nightly"),
+        }
+    }
+}
+
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -229,13 +387,38 @@ impl fmt::Display for VersionReq {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             VersionReq::Latest => f.write_str("latest"),
+            VersionReq::Prerelease => f.write_str("latest prerelease"),
             VersionReq::Nightly => f.write_str("nightly"),
+            VersionReq::ExactNightly(date) => write!(f, "nightly.{}", date.format(DATEFMT)),
+            VersionReq::NightlyRange(bounds) => {
+                f.write_str("nightly")?;
+                for (i, bound) in bounds.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", bound)?;
+                }
+                Ok(())
+            }
             VersionReq::Stable(version_req) => f.write_str(&version_req.to_string()),
             VersionReq::ExactStable(version) => f.write_str(&version.to_string()),
         }
     }
 }
 
+impl fmt::Display for DateBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            DateOp::Greater => ">",
+            DateOp::GreaterEq => ">=",
+            DateOp::Less => "<",
+            DateOp::LessEq => "<=",
+            DateOp::Eq => "=",
+        };
+        write!(f, "{}{}", op, self.date.format(DATEFMT))
+    }
+}
+
 impl PartialOrd for Version {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         let res = match self {
@@ -257,3 +440,100 @@ impl Ord for Version {
         self.partial_cmp(other).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn nightly(date: &str) -> Version {
+        Version::Nightly(Some(parse_date(date).unwrap()))
+    }
+
+    fn stable(version: &str) -> Version {
+        Version::Stable(SemverVersion::parse(version).unwrap())
+    }
+
+    #[test]
+    fn date_bounds() {
+        let date = Utc.ymd(2020, 6, 1);
+        for (bound, op) in &[
+            (">=20200601", DateOp::GreaterEq),
+            ("<=20200601", DateOp::LessEq),
+            (">20200601", DateOp::Greater),
+            ("<20200601", DateOp::Less),
+            ("=20200601", DateOp::Eq),
+            (">= 20200601", DateOp::GreaterEq),
+        ] {
+            assert_eq!(DateBound::parse(bound), Some(DateBound { op: *op, date }));
+        }
+        assert_eq!(DateBound::parse("20200601"), None);
+        assert_eq!(DateBound::parse(">=2020-06-01"), None);
+        assert_eq!(DateBound::parse(">=20201301"), None);
+        assert_eq!(DateBound::parse(">="), None);
+    }
+
+    #[test]
+    fn nightly_requirements() {
+        assert!(matches!(
+            VersionReq::parse("nightly"),
+            Ok(VersionReq::Nightly)
+        ));
+
+        let exact = VersionReq::parse("nightly.20200601").unwrap();
+        assert!(exact.matches(&nightly("20200601")));
+        assert!(!exact.matches(&nightly("20200602")));
+        assert!(!exact.matches(&Version::Nightly(None)));
+
+        let range = VersionReq::parse("nightly>=20200601,<20200701").unwrap();
+        assert_eq!(range.to_string(), "nightly>=20200601,<20200701");
+        assert!(range.matches(&nightly("20200601")));
+        assert!(range.matches(&nightly("20200630")));
+        assert!(!range.matches(&nightly("20200701")));
+        assert!(!range.matches(&stable("0.9.0")));
+
+        for invalid in &[
+            "nightly.2020",
+            "nightly>=20200601,",
+            "nightly-latest",
+            "nightly20200601",
+        ] {
+            assert!(
+                matches!(VersionReq::parse(invalid), Err(VersionReqError::Nightly(_))),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn stable_requirements() {
+        let exact = VersionReq::parse("0.9.0").unwrap();
+        assert!(matches!(exact, VersionReq::ExactStable(_)));
+        assert!(exact.matches(&stable("0.9.0")));
+        assert!(exact.matches(&stable("0.9.0+1a2b3c4")));
+        assert!(!exact.matches(&stable("0.9.1")));
+        assert_eq!(exact.channel(), Channel::Stable);
+
+        let built = VersionReq::parse("0.9.0+1a2b3c4").unwrap();
+        assert!(built.matches(&stable("0.9.0+1a2b3c4")));
+        assert!(!built.matches(&stable("0.9.0+5d6e7f8")));
+
+        let req = VersionReq::parse(">=0.8.18").unwrap();
+        assert!(matches!(req, VersionReq::Stable(_)));
+        assert!(req.matches(&stable("0.9.0")));
+        assert!(!req.matches(&stable("0.8.17")));
+        assert!(!req.matches(&nightly("20200601")));
+        assert_eq!(req.channel(), Channel::Stable);
+
+        let rc = VersionReq::parse(">=0.9.0-rc1").unwrap();
+        assert_eq!(rc.channel(), Channel::Prerelease);
+        assert_eq!(
+            VersionReq::parse("0.9.0-rc1").unwrap().channel(),
+            Channel::Prerelease
+        );
+
+        assert!(VersionReq::parse("0.9").is_err());
+        assert!(VersionReq::parse(">=abc").is_err());
+    }
+}